@@ -0,0 +1,15 @@
+//!
+//! Feed arbitrary bytes to the COFF reader, which must return an error
+//! rather than panic however the file is damaged. Run with
+//! `cargo fuzz run read` from the we32dis directory.
+//!
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use we32dis::coff::FileContainer;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = FileContainer::read(data);
+    let _ = FileContainer::read_lenient(data);
+});
@@ -0,0 +1,257 @@
+//!
+//! Reading of `ar` archives, such as 3b2 libraries
+//!
+//! An archive is the magic string "!<arch>\n" followed by a sequence of
+//! members. Each member has a 60-byte ASCII header and is padded to an
+//! even length. Long member names are handled in both the System V/GNU
+//! style (a "//" member holding a table of names, referenced as "/N")
+//! and the BSD style ("#1/N", with the name prefixed to the data).
+//!
+
+use std::convert::TryFrom;
+use std::str;
+
+use crate::errors::{CoffError, ReadResult};
+
+const ARCHIVE_MAGIC: &[u8] = b"!<arch>\n";
+const MEMBER_HEADER_SIZE: usize = 60;
+const MEMBER_MAGIC: &[u8] = b"`\n";
+
+pub struct ArchiveMember<'a> {
+    pub name: String,
+    pub date: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u32,
+    /// Offset of `data` from the start of the archive
+    pub offset: usize,
+    pub data: &'a [u8],
+}
+
+pub struct Archive<'a> {
+    pub members: Vec<ArchiveMember<'a>>,
+}
+
+/// True if the buffer starts with the archive magic.
+pub fn is_archive(buf: &[u8]) -> bool {
+    buf.starts_with(ARCHIVE_MAGIC)
+}
+
+///
+/// Parse a numeric header field, which is ASCII padded with spaces.
+/// An all-blank field is zero.
+///
+fn header_number(field: &[u8], radix: u32) -> ReadResult<u64> {
+    let text = str::from_utf8(field)?.trim();
+
+    if text.is_empty() {
+        return Ok(0);
+    }
+
+    u64::from_str_radix(text, radix).map_err(|_| CoffError::BadArchive)
+}
+
+impl<'a> Archive<'a> {
+    pub fn read(buf: &'a [u8]) -> ReadResult<Self> {
+        if !is_archive(buf) {
+            return Err(CoffError::BadArchive);
+        }
+
+        let mut members = vec!();
+        let mut long_names: &[u8] = &[];
+        let mut offset = ARCHIVE_MAGIC.len();
+
+        while offset < buf.len() {
+            let header = buf.get(offset..offset + MEMBER_HEADER_SIZE)
+                .ok_or(CoffError::BadArchive)?;
+
+            if &header[58..60] != MEMBER_MAGIC {
+                return Err(CoffError::BadArchive);
+            }
+
+            let raw_name = str::from_utf8(&header[0..16])?.trim_end();
+            let date = header_number(&header[16..28], 10)?;
+            let uid = u32::try_from(header_number(&header[28..34], 10)?)
+                .map_err(|_| CoffError::BadArchive)?;
+            let gid = u32::try_from(header_number(&header[34..40], 10)?)
+                .map_err(|_| CoffError::BadArchive)?;
+            let mode = u32::try_from(header_number(&header[40..48], 8)?)
+                .map_err(|_| CoffError::BadArchive)?;
+            let size = usize::try_from(header_number(&header[48..58], 10)?)
+                .map_err(|_| CoffError::BadArchive)?;
+
+            let start = offset + MEMBER_HEADER_SIZE;
+            let end = start.checked_add(size)
+                .filter(|&end| end <= buf.len())
+                .ok_or(CoffError::BadArchive)?;
+            let mut data = &buf[start..end];
+            let mut data_offset = start;
+
+            // Members are aligned on even offsets.
+            offset = end + (end & 1);
+
+            let name = if raw_name == "/" || raw_name == "__.SYMDEF" {
+                // Symbol lookup table for the link editor, not a member.
+                continue;
+            } else if raw_name == "//" {
+                long_names = data;
+                continue;
+            } else if let Some(len) = raw_name.strip_prefix("#1/") {
+                // BSD long name, stored at the front of the data.
+                let len: usize = len.parse().map_err(|_| CoffError::BadArchive)?;
+                if len > data.len() {
+                    return Err(CoffError::BadArchive);
+                }
+                let name = str::from_utf8(&data[..len])?.trim_end_matches('\0').to_owned();
+                data = &data[len..];
+                data_offset += len;
+                name
+            } else if let Some(index) = raw_name.strip_prefix('/') {
+                // System V/GNU long name, an offset into the "//" member.
+                let index: usize = index.parse().map_err(|_| CoffError::BadArchive)?;
+                let rest = long_names.get(index..).ok_or(CoffError::BadArchive)?;
+                let len = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+                str::from_utf8(&rest[..len])?.trim_end_matches('/').to_owned()
+            } else {
+                raw_name.trim_end_matches('/').to_owned()
+            };
+
+            members.push(ArchiveMember {
+                name,
+                date,
+                uid,
+                gid,
+                mode,
+                offset: data_offset,
+                data,
+            });
+        }
+
+        Ok(Archive { members })
+    }
+
+    /// Find a member by name.
+    pub fn member(&self, name: &str) -> Option<&ArchiveMember<'a>> {
+        self.members.iter().find(|m| m.name == name)
+    }
+
+    /// Print a one-line summary of each member to stdout.
+    pub fn list_members(&self) {
+        for member in &self.members {
+            println!("{:>8}  {:o}  {}", member.data.len(), member.mode, member.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A member header and its data, padded to an even length.
+    fn member(name: &str, size: usize, data: &[u8]) -> Vec<u8> {
+        let header = format!("{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n", name, 0, 100, 10, 644, size);
+        assert_eq!(header.len(), MEMBER_HEADER_SIZE);
+
+        let mut buf = header.into_bytes();
+        buf.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            buf.push(b'\n');
+        }
+        buf
+    }
+
+    fn archive(members: &[Vec<u8>]) -> Vec<u8> {
+        let mut buf = ARCHIVE_MAGIC.to_vec();
+        for member in members {
+            buf.extend_from_slice(member);
+        }
+        buf
+    }
+
+    #[test]
+    fn short_names() {
+        let buf = archive(&[
+            member("/", 4, &[0, 0, 0, 0]),
+            member("a.o/", 2, b"ab"),
+            member("b.o", 2, b"cd"),
+        ]);
+        let archive = Archive::read(&buf).unwrap();
+
+        let names: Vec<_> = archive.members.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["a.o", "b.o"]);
+
+        let a = archive.member("a.o").unwrap();
+        assert_eq!((a.uid, a.gid, a.mode), (100, 10, 0o644));
+        assert_eq!(a.data, b"ab");
+    }
+
+    #[test]
+    fn system_v_long_names() {
+        let table = b"a_very_long_member_name.o/\nanother_long_name.o/\n";
+        let buf = archive(&[
+            member("//", table.len(), table),
+            member("/27", 2, b"ab"),
+            member("/0", 2, b"cd"),
+        ]);
+        let archive = Archive::read(&buf).unwrap();
+
+        assert_eq!(archive.member("another_long_name.o").unwrap().data, b"ab");
+        assert_eq!(archive.member("a_very_long_member_name.o").unwrap().data, b"cd");
+    }
+
+    #[test]
+    fn system_v_long_name_outside_table_is_an_error() {
+        let buf = archive(&[member("//", 4, b"a.o\n"), member("/10", 2, b"ab")]);
+        assert!(matches!(Archive::read(&buf), Err(CoffError::BadArchive)));
+    }
+
+    #[test]
+    fn bsd_long_names() {
+        // A 20-byte name, padded with NULs, then 2 bytes of data.
+        let buf = archive(&[member("#1/20", 22, b"bsd_long_name.o\0\0\0\0\0ab")]);
+        let archive = Archive::read(&buf).unwrap();
+
+        let member = archive.member("bsd_long_name.o").unwrap();
+        assert_eq!(member.data, b"ab");
+        assert_eq!(member.offset, ARCHIVE_MAGIC.len() + MEMBER_HEADER_SIZE + 20);
+    }
+
+    #[test]
+    fn odd_length_member_is_padded() {
+        let buf = archive(&[member("a.o", 3, b"abc"), member("b.o", 2, b"de")]);
+        let archive = Archive::read(&buf).unwrap();
+
+        assert_eq!(archive.member("a.o").unwrap().data, b"abc");
+
+        // After the first header, 3 bytes of data, a pad byte and the
+        // second header.
+        let b = archive.member("b.o").unwrap();
+        assert_eq!(b.data, b"de");
+        assert_eq!(b.offset, ARCHIVE_MAGIC.len() + 2 * MEMBER_HEADER_SIZE + 4);
+    }
+
+    #[test]
+    fn truncated_header_is_an_error() {
+        let mut buf = archive(&[member("a.o", 2, b"ab")]);
+        buf.extend_from_slice(&member("b.o", 2, b"cd")[..30]);
+
+        assert!(matches!(Archive::read(&buf), Err(CoffError::BadArchive)));
+    }
+
+    #[test]
+    fn oversized_member_is_an_error() {
+        let buf = archive(&[member("a.o", 100, b"ab")]);
+        assert!(matches!(Archive::read(&buf), Err(CoffError::BadArchive)));
+
+        let buf = archive(&[member("a.o", 9_999_999_999, b"ab")]);
+        assert!(matches!(Archive::read(&buf), Err(CoffError::BadArchive)));
+    }
+
+    #[test]
+    fn bad_numbers_are_errors() {
+        let mut buf = archive(&[member("a.o", 2, b"ab")]);
+        buf[ARCHIVE_MAGIC.len() + 28] = b'x';
+
+        assert!(matches!(Archive::read(&buf), Err(CoffError::BadArchive)));
+    }
+}
@@ -1,7 +1,8 @@
-///
-/// WE32000 COFF File Parsing and Utilities
-///
+//!
+//! WE32000 COFF File Parsing and Utilities
+//!
 
+use std::convert::TryFrom;
 use std::str::Utf8Error;
 use std::fmt;
 use std::io::Cursor;
@@ -10,12 +11,18 @@ use std::io::{Read, Seek, SeekFrom};
 use std::str;
 
 use crate::errors::{CoffError, ReadResult, OffsetError};
+use crate::render::json_escape;
+use crate::symbols::SymbolMap;
 
 use chrono::prelude::*;
 use chrono::TimeZone;
 
 use byteorder::{BigEndian, ReadBytesExt};
+#[cfg(feature = "hash")]
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ops::Range;
 
 // WE32000 without transfer vector
 const MAGIC_WE32K: u16 = 0x170;
@@ -23,9 +30,24 @@ const MAGIC_WE32K: u16 = 0x170;
 // WE32000 with transfer vector
 const MAGIC_WE32K_TV: u16 = 0x171;
 
+// Optional header magic numbers, in the a.out tradition
+const AOUT1MAGIC: u16 = 0o407; // Text and data contiguous, not shared
+const AOUT2MAGIC: u16 = 0o410; // Text is write-protected and shared
+const PAGEMAGIC: u16 = 0o413;  // Demand paged, shared text
+const LIBMAGIC: u16 = 0o443;   // Target shared library
+
 // Size of the file header
 const FILE_HEADER_SIZE: u16 = 20;
 
+// Special symbol section numbers
+const N_UNDEF: i16 = 0;  // Undefined or external symbol
+const N_ABS: i16 = -1;   // Absolute symbol
+const N_DEBUG: i16 = -2; // Debugging symbol
+
+// Derived types of a function and an array, in bits 4-5 of n_type
+const DT_FCN: u16 = 2;
+const DT_ARY: u16 = 3;
+
 // Length of old COFF version symbol names
 const SYM_NAME_LEN: usize = 8;
 
@@ -52,11 +74,84 @@ bitflags! {
     }
 }
 
+// Section Header flags
+bitflags! {
+    pub struct SectionFlags: u32 {
+        // Dummy section; relocated but not allocated or loaded
+        const STYP_DSECT = 0x0001;
+        // Allocated and relocated but not loaded
+        const STYP_NOLOAD = 0x0002;
+        // Grouped section, formed by the link editor
+        const STYP_GROUP = 0x0004;
+        // Padding section; loaded but not allocated
+        const STYP_PAD = 0x0008;
+        // Copy section; loaded but not allocated or relocated
+        const STYP_COPY = 0x0010;
+        // Section contains executable text
+        const STYP_TEXT = 0x0020;
+        // Section contains initialized data
+        const STYP_DATA = 0x0040;
+        // Section contains uninitialized data
+        const STYP_BSS = 0x0080;
+        // Comment section; not allocated, relocated, or loaded
+        const STYP_INFO = 0x0200;
+        // Overlay section; relocated but not allocated or loaded
+        const STYP_OVER = 0x0400;
+        // Target shared library section
+        const STYP_LIB = 0x0800;
+    }
+}
+
+impl SectionFlags {
+    ///
+    /// Short names of the set flags, e.g. "TEXT" or "DATA|COPY". A
+    /// regular section with no flags set is "REG".
+    ///
+    pub fn names(self) -> String {
+        self.joined_names("")
+    }
+
+    ///
+    /// Names of the set flags as the COFF headers define them, e.g.
+    /// "STYP_TEXT" or "STYP_DATA|STYP_COPY". A regular section with no
+    /// flags set is "STYP_REG".
+    ///
+    pub fn styp_names(self) -> String {
+        self.joined_names("STYP_")
+    }
+
+    fn joined_names(self, prefix: &str) -> String {
+        const NAMES: [(SectionFlags, &str); 11] = [
+            (SectionFlags::STYP_TEXT, "TEXT"),
+            (SectionFlags::STYP_DATA, "DATA"),
+            (SectionFlags::STYP_BSS, "BSS"),
+            (SectionFlags::STYP_DSECT, "DSECT"),
+            (SectionFlags::STYP_NOLOAD, "NOLOAD"),
+            (SectionFlags::STYP_GROUP, "GROUP"),
+            (SectionFlags::STYP_PAD, "PAD"),
+            (SectionFlags::STYP_COPY, "COPY"),
+            (SectionFlags::STYP_INFO, "INFO"),
+            (SectionFlags::STYP_OVER, "OVER"),
+            (SectionFlags::STYP_LIB, "LIB"),
+        ];
+
+        if self.is_empty() {
+            return format!("{}REG", prefix);
+        }
+
+        NAMES.iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| format!("{}{}", prefix, name))
+            .collect::<Vec<String>>()
+            .join("|")
+    }
+}
+
 pub struct FileHeader {
     pub magic: u16,
     pub section_count: u16,
     pub timestamp: u32,
-    pub datetime: DateTime<Utc>,
+    pub datetime: Option<DateTime<Utc>>,
     pub symbol_table_offset: u32,
     pub symbol_count: u32,
     pub opt_header: u16,
@@ -68,11 +163,153 @@ fn buf_to_str(buf: &[u8]) -> Result<&str, std::str::Utf8Error> {
     str::from_utf8(&buf[0..nul])
 }
 
+// Like buf_to_str, but never fails. A name that fills the buffer with
+// no NUL is taken whole, and bytes that aren't valid UTF-8 (such as
+// Latin-1 characters) are shown escaped, e.g. "\xe9".
+fn buf_to_str_lossy(buf: &[u8]) -> Cow<'_, str> {
+    let nul = buf.iter().position( |&c| c == b'\0').unwrap_or(buf.len());
+    let bytes = &buf[0..nul];
+
+    match str::from_utf8(bytes) {
+        Ok(s) => Cow::Borrowed(s),
+        Err(_) => {
+            let mut s = String::new();
+            let mut rest = bytes;
+
+            while !rest.is_empty() {
+                match str::from_utf8(rest) {
+                    Ok(valid) => {
+                        s.push_str(valid);
+                        break;
+                    }
+                    Err(e) => {
+                        let (valid, invalid) = rest.split_at(e.valid_up_to());
+                        s.push_str(str::from_utf8(valid).unwrap_or_default());
+                        let bad = e.error_len().unwrap_or(invalid.len());
+                        for b in &invalid[..bad] {
+                            s.push_str(&format!("\\x{:02x}", b));
+                        }
+                        rest = &invalid[bad..];
+                    }
+                }
+            }
+
+            Cow::Owned(s)
+        }
+    }
+}
+
+/// How the bytes of symbol names and file names are turned into text.
+/// The COFF format doesn't say, and files built on systems set up for
+/// other languages may use an 8-bit character set such as Latin-1.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NameEncoding {
+    /// UTF-8, with invalid bytes escaped, e.g. "\xe9"
+    #[default]
+    Utf8,
+    /// ISO 8859-1, where each byte is the character of that number
+    Latin1,
+    /// ASCII, with every other byte escaped
+    Raw,
+}
+
+// Turn a NUL-terminated name into text in the given encoding. As with
+// buf_to_str_lossy, a name with no NUL is taken whole.
+fn decode_name(buf: &[u8], encoding: NameEncoding) -> Cow<'_, str> {
+    let nul = buf.iter().position( |&c| c == b'\0').unwrap_or(buf.len());
+    let bytes = &buf[0..nul];
+
+    match encoding {
+        NameEncoding::Utf8 => buf_to_str_lossy(bytes),
+        _ if bytes.is_ascii() => Cow::Borrowed(str::from_utf8(bytes).unwrap_or_default()),
+        NameEncoding::Latin1 => Cow::Owned(bytes.iter().map(|&b| char::from(b)).collect()),
+        NameEncoding::Raw => {
+            let mut s = String::new();
+            for &b in bytes {
+                if b.is_ascii() {
+                    s.push(char::from(b));
+                } else {
+                    s.push_str(&format!("\\x{:02x}", b));
+                }
+            }
+            Cow::Owned(s)
+        }
+    }
+}
+
+///
+/// Find runs of at least `min_len` printable ASCII characters (including
+/// tab) in `data`, as strings(1) does. Returns the offset of each run
+/// along with its text.
+///
+pub fn ascii_strings(data: &[u8], min_len: usize) -> Vec<(usize, &str)> {
+    let printable = |b: &u8| (0x20..0x7f).contains(b) || *b == b'\t';
+    let mut found = vec!();
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let len = data[offset..].iter().take_while(|b| printable(b)).count();
+
+        if len > 0 && len >= min_len {
+            if let Ok(text) = str::from_utf8(&data[offset..offset + len]) {
+                found.push((offset, text));
+            }
+        }
+
+        offset += len.max(1);
+    }
+
+    found
+}
+
+///
+/// Format one row of a section data dump: the address, up to 16 bytes
+/// in hex and the same bytes as ASCII. A short final row is padded so
+/// that its summary lines up with the rows above it.
+///
+fn data_row(vaddr: u32, bytes: &[u8]) -> String {
+    let mut row = format!("        {:08x}:   ", vaddr);
+
+    for x in 0..16 {
+        match bytes.get(x) {
+            Some(b) => row.push_str(&format!("{:02x} ", b)),
+            None => row.push_str("   "),
+        }
+
+        if x == 7 {
+            row.push_str("  ");
+        }
+    }
+
+    // Then the human-readable summary of only this row's bytes.
+    row.push_str("  | ");
+
+    for x in 0..16 {
+        row.push(match bytes.get(x) {
+            Some(c) if *c >= 0x20 && *c < 0x7f => *c as char,
+            Some(_) => '.',
+            None => ' ',
+        });
+    }
+
+    row.push_str(" |");
+    row
+}
+
+// Returns true if `len` bytes starting at `offset` fit entirely within
+// a buffer of `buf_len` bytes. All of the offsets and counts we check
+// come straight out of the file, so they must never be trusted.
+fn in_bounds(buf_len: usize, offset: u64, len: u64) -> bool {
+    match offset.checked_add(len) {
+        Some(end) => end <= buf_len as u64,
+        None => false,
+    }
+}
+
 impl FileHeader {
     ///
     /// Read a FileHeader from the current cursor position.
     ///
-
     pub fn read(cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
         let magic = cursor.read_u16::<BigEndian>()?;
         let section_count = cursor.read_u16::<BigEndian>()?;
@@ -81,7 +318,12 @@ impl FileHeader {
         let symbol_count = cursor.read_u32::<BigEndian>()?;
         let opt_header = cursor.read_u16::<BigEndian>()?;
         let flags = FileHeaderFlags::from_bits_truncate(cursor.read_u16::<BigEndian>()?);
-        let datetime = Utc.timestamp(i64::from(timestamp), 0);
+        // A zero timestamp means the field was never set (or was
+        // deliberately zeroed), not that the file dates from 1970.
+        let datetime = match timestamp {
+            0 => None,
+            t => Utc.timestamp_opt(i64::from(t), 0).single(),
+        };
 
         let header = FileHeader {
             magic,
@@ -112,44 +354,139 @@ impl FileHeader {
     pub fn mau_required(&self) -> bool {
         self.flags.contains(FileHeaderFlags::F_BM32MAU)
     }
+
+    ///
+    /// Format the header's timestamp, or "not set" if it is zero.
+    ///
+    pub fn date_string(&self, format: TimeFormat) -> String {
+        match self.datetime {
+            Some(dt) => match format {
+                TimeFormat::Rfc2822 => dt.to_rfc2822(),
+                TimeFormat::Iso8601 => dt.to_rfc3339(),
+            },
+            None => String::from("not set"),
+        }
+    }
+
+    ///
+    /// Return a value that formats the header using the given
+    /// timestamp format.
+    ///
+    pub fn display(&self, time_format: TimeFormat) -> FileHeaderDisplay<'_> {
+        FileHeaderDisplay {
+            header: self,
+            time_format,
+        }
+    }
+}
+
+/// How to format timestamps in header dumps.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TimeFormat {
+    Rfc2822,
+    Iso8601,
+}
+
+pub struct FileHeaderDisplay<'a> {
+    header: &'a FileHeader,
+    time_format: TimeFormat,
 }
 
 impl fmt::Debug for FileHeader {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.display(TimeFormat::Rfc2822))
+    }
+}
+
+impl<'a> fmt::Display for FileHeaderDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = self.header;
+
         writeln!(f, "COFF File Header:")?;
 
-        let magic = match self.magic {
+        let magic = match header.magic {
             MAGIC_WE32K | MAGIC_WE32K_TV => "WE32000",
             _ => "Unknown"
         };
 
         write!(f, "{}", magic)?;
 
-        if self.executable() {
+        if header.executable() {
             write!(f, " executable")?;
         }
 
-        if !self.local_symbols_stripped() {
+        if !header.local_symbols_stripped() {
             write!(f, " not stripped")?;
         }
 
-        if self.is_32100_required() {
+        if header.is_32100_required() {
             write!(f, ", 32100 required")?;
         }
 
-        if self.mau_required() {
+        if header.mau_required() {
             write!(f, ", MAU hardware required")?;
         }
 
         writeln!(f, ".")?;
 
-        writeln!(f, "    Magic Number:  0x{:04x}", self.magic)?;
-        writeln!(f, "    Num Sections:  {}", self.section_count)?;
-        writeln!(f, "    Date:          {}", self.datetime.to_rfc2822())?;
-        writeln!(f, "    Symbols Ptr:   0x{:x}", self.symbol_table_offset)?;
-        writeln!(f, "    Symbol Count:  {}", self.symbol_count)?;
-        writeln!(f, "    Opt Hdr:       {:?}", self.opt_header == 0x1c)?;
-        write!(f, "    Flags:         0x{:04x}", self.flags)
+        writeln!(f, "    Magic Number:  0x{:04x}", header.magic)?;
+        writeln!(f, "    Num Sections:  {}", header.section_count)?;
+        writeln!(f, "    Date:          {}", header.date_string(self.time_format))?;
+        writeln!(f, "    Symbols Ptr:   0x{:x}", header.symbol_table_offset)?;
+        writeln!(f, "    Symbol Count:  {}", header.symbol_count)?;
+        writeln!(f, "    Opt Hdr:       {:?}", header.opt_header == 0x1c)?;
+        write!(f, "    Flags:         0x{:04x}", header.flags)
+    }
+}
+
+/// The layout of an executable, as given by the optional header magic.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AoutMagic {
+    /// 0407: text and data are loaded contiguously and are writable
+    Impure,
+    /// 0410: text is read-only and may be shared; data starts on the
+    /// next segment boundary
+    SharedText,
+    /// 0413: demand paged with shared text; sections are page aligned
+    /// in the file so they can be mapped directly
+    DemandPaged,
+    /// 0443: a target shared library
+    SharedLibrary,
+    Unknown(u16),
+}
+
+impl AoutMagic {
+    pub fn from_u16(magic: u16) -> Self {
+        match magic {
+            AOUT1MAGIC => AoutMagic::Impure,
+            AOUT2MAGIC => AoutMagic::SharedText,
+            PAGEMAGIC => AoutMagic::DemandPaged,
+            LIBMAGIC => AoutMagic::SharedLibrary,
+            m => AoutMagic::Unknown(m),
+        }
+    }
+
+    pub fn is_shared_text(self) -> bool {
+        match self {
+            AoutMagic::SharedText | AoutMagic::DemandPaged | AoutMagic::SharedLibrary => true,
+            AoutMagic::Impure | AoutMagic::Unknown(_) => false,
+        }
+    }
+
+    pub fn is_demand_paged(self) -> bool {
+        self == AoutMagic::DemandPaged
+    }
+}
+
+impl fmt::Display for AoutMagic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AoutMagic::Impure => write!(f, "impure"),
+            AoutMagic::SharedText => write!(f, "shared text"),
+            AoutMagic::DemandPaged => write!(f, "demand paged"),
+            AoutMagic::SharedLibrary => write!(f, "shared library"),
+            AoutMagic::Unknown(_) => write!(f, "unknown"),
+        }
     }
 }
 
@@ -180,12 +517,17 @@ impl OptionalHeader {
 
         Ok(header)
     }
+
+    /// Decode the magic number into the executable layout.
+    pub fn aout_magic(&self) -> AoutMagic {
+        AoutMagic::from_u16(self.magic)
+    }
 }
 
 impl fmt::Debug for OptionalHeader {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "Optional Header:")?;
-        writeln!(f, "    Magic Number:    0x{:04x}", self.magic)?;
+        writeln!(f, "    Magic Number:    0{:o} ({})", self.magic, self.aout_magic())?;
         writeln!(f, "    Version Stamp:   0x{:04x}", self.version_stamp)?;
         writeln!(f, "    Text Size:       0x{:x}", self.text_size)?;
         writeln!(f, "    dsize:           0x{:x}", self.dsize)?;
@@ -229,11 +571,21 @@ impl SectionHeader {
 
         Ok(header)
     }
+
+    pub fn name(&self) -> Cow<'_, str> {
+        buf_to_str_lossy(&self.name)
+    }
+
+    /// The known flag bits of the section. Unknown bits are dropped;
+    /// the raw value is still available in `flags`.
+    pub fn section_flags(&self) -> SectionFlags {
+        SectionFlags::from_bits_truncate(self.flags)
+    }
 }
 
 impl fmt::Debug for SectionHeader {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let name = buf_to_str(&self.name).unwrap_or("???");
+        let name = self.name();
 
         writeln!(f, "Section Header:")?;
         writeln!(f, "    Name:              {}", name)?;
@@ -245,10 +597,31 @@ impl fmt::Debug for SectionHeader {
         writeln!(f, "    Line Num. Offset:  0x{:x}", self.lnnoptr)?;
         writeln!(f, "    Rel. Tab. Entries: {}", self.nreloc)?;
         writeln!(f, "    Line Num. Entries: {}", self.nlnno)?;
-        write!(f, "    Flags:             0x{:08x}", self.flags)
+        write!(f, "    Flags:             0x{:08x} ({})", self.flags, self.section_flags().styp_names())
     }
 }
 
+/// Representation of a Line Number Table Entry
+///
+/// The entries for each function start with one whose `lnno` is zero
+/// and whose `addr` is the symbol table index of the function. In the
+/// entries that follow, `addr` is the address of the code for source
+/// line `lnno`, counted from 1 at the function's opening line (given by
+/// the aux entry of its ".bf" symbol).
+pub struct LineNumber {
+    pub addr: u32,
+    pub lnno: u16,
+}
+
+/// A source line located by the line number table: the address of its
+/// code, the source file it's in if known, and its line number in that
+/// file.
+pub struct SourceLine {
+    pub addr: u32,
+    pub file: Option<String>,
+    pub line: u32,
+}
+
 /// Representation of a Relocation Table Entry
 pub struct RelocationEntry {
     pub vaddr: u32,
@@ -256,6 +629,35 @@ pub struct RelocationEntry {
     pub rtype: u16,
 }
 
+impl RelocationEntry {
+    ///
+    /// The name of the relocation type, e.g. "R_DIR32", and a short
+    /// description of the reference it patches, or `None` if the type
+    /// is not one the link editor defines.
+    ///
+    pub fn type_info(&self) -> Option<(&'static str, &'static str)> {
+        let info = match self.rtype {
+            0o00 => ("R_ABS", "absolute, not relocated"),
+            0o01 => ("R_DIR16", "16-bit direct reference to"),
+            0o02 => ("R_REL16", "16-bit relative reference to"),
+            0o03 => ("R_IND16", "16-bit indirect reference to"),
+            0o04 => ("R_DIR24", "24-bit direct reference to"),
+            0o05 => ("R_REL24", "24-bit relative reference to"),
+            0o06 => ("R_DIR32", "32-bit direct reference to"),
+            0o12 => ("R_DIR32S", "32-bit byte-swapped direct reference to"),
+            0o17 => ("R_RELBYTE", "8-bit direct reference to"),
+            0o20 => ("R_RELWORD", "16-bit direct reference to"),
+            0o21 => ("R_RELLONG", "32-bit direct reference to"),
+            0o22 => ("R_PCRBYTE", "8-bit PC-relative reference to"),
+            0o23 => ("R_PCRWORD", "16-bit PC-relative reference to"),
+            0o24 => ("R_PCRLONG", "32-bit PC-relative reference to"),
+            _ => return None,
+        };
+
+        Some(info)
+    }
+}
+
 ///// Applicaple only to primary symbols.
 //pub enum SymbolType {
 //    None,
@@ -329,6 +731,42 @@ impl fmt::Debug for StorageClass {
     }
 }
 
+impl StorageClass {
+    /// The class's name in the C headers, e.g. "C_EXT". Symbols of
+    /// unknown classes are read as `Null`, so they give "C_NULL".
+    pub fn name(&self) -> &'static str {
+        match *self {
+            StorageClass::EndOfFunction => "C_EFCN",
+            StorageClass::Null => "C_NULL",
+            StorageClass::Auto => "C_AUTO",
+            StorageClass::ExternalSym => "C_EXT",
+            StorageClass::Static => "C_STAT",
+            StorageClass::Register => "C_REG",
+            StorageClass::ExternalDef => "C_EXTDEF",
+            StorageClass::Label => "C_LABEL",
+            StorageClass::UndefinedLabel => "C_ULABEL",
+            StorageClass::MemberOfStruct => "C_MOS",
+            StorageClass::FunctionArg => "C_ARG",
+            StorageClass::StructureTag => "C_STRTAG",
+            StorageClass::MemberOfUnion => "C_MOU",
+            StorageClass::UnionTag => "C_UNTAG",
+            StorageClass::TypeDefinition => "C_TPDEF",
+            StorageClass::UninitializedStatic => "C_USTATIC",
+            StorageClass::EnumerationTag => "C_ENTAG",
+            StorageClass::MemberOfEnumeration => "C_MOE",
+            StorageClass::RegisterParameter => "C_REGPARM",
+            StorageClass::BitField => "C_FIELD",
+            StorageClass::BeginEndBlock => "C_BLOCK",
+            StorageClass::BeginEndFunc => "C_FCN",
+            StorageClass::EndOfStruct => "C_EOS",
+            StorageClass::Filename => "C_FILE",
+            StorageClass::Line => "C_LINE",
+            StorageClass::Alias => "C_ALIAS",
+            StorageClass::Hidden => "C_HIDDEN",
+        }
+    }
+}
+
 /// Representation of a Symbol Table Entry
 pub enum Symbol {
     Primary {
@@ -395,10 +833,166 @@ pub enum Symbol {
 
 pub struct SymbolTableEntry {
     symbol: Symbol,
+    // For auxiliary entries, the index of the primary symbol they belong to.
+    parent: Option<usize>,
+}
+
+///
+/// An auxiliary entry, read as the union its primary symbol calls for:
+/// a file symbol's entry holds the file name, a function's its size
+/// and line number pointer, and an array's its dimensions. Any other
+/// entry keeps every field but the dimensions.
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AuxEntry<'a> {
+    File {
+        name: &'a str,
+    },
+    Function {
+        tagndx: u32,
+        fsize: u32,
+        lnnoptr: u32,
+        endndx: u32,
+        tvndx: u16,
+    },
+    Array {
+        tagndx: u32,
+        lnno: u16,
+        size: u16,
+        dimen: [u16; 4],
+        tvndx: u16,
+    },
+    Other {
+        tagndx: u32,
+        lnno: u16,
+        size: u16,
+        lnnoptr: u32,
+        endndx: u32,
+        tvndx: u16,
+    },
+}
+
+impl<'a> AuxEntry<'a> {
+    ///
+    /// Read `aux`, an auxiliary entry of a primary symbol whose type is
+    /// `n_type`. Returns `None` if `aux` is a primary symbol itself.
+    ///
+    pub fn new(aux: &'a Symbol, n_type: u16) -> Option<Self> {
+        let entry = match aux {
+            Symbol::Primary { .. } => return None,
+            Symbol::Auxiliary { x_fname: Some(name), .. } => AuxEntry::File { name },
+            Symbol::Auxiliary { x_tagndx, x_fsize, x_lnnoptr, x_endndx, x_tvndx, .. }
+                if (n_type >> 4) & 3 == DT_FCN => AuxEntry::Function {
+                tagndx: *x_tagndx,
+                fsize: *x_fsize,
+                lnnoptr: *x_lnnoptr,
+                endndx: *x_endndx,
+                tvndx: *x_tvndx,
+            },
+            Symbol::Auxiliary { x_tagndx, x_lnno, x_size, x_dimen, x_tvndx, .. }
+                if (n_type >> 4) & 3 == DT_ARY => AuxEntry::Array {
+                tagndx: *x_tagndx,
+                lnno: *x_lnno,
+                size: *x_size,
+                dimen: *x_dimen,
+                tvndx: *x_tvndx,
+            },
+            Symbol::Auxiliary { x_tagndx, x_lnno, x_size, x_lnnoptr, x_endndx, x_tvndx, .. } => AuxEntry::Other {
+                tagndx: *x_tagndx,
+                lnno: *x_lnno,
+                size: *x_size,
+                lnnoptr: *x_lnnoptr,
+                endndx: *x_endndx,
+                tvndx: *x_tvndx,
+            },
+        };
+
+        Some(entry)
+    }
+}
+
+/// A primary symbol with its name resolved and its auxiliary entries
+/// attached.
+pub struct ResolvedSymbol<'a> {
+    pub index: usize,
+    pub name: String,
+    pub value: u32,
+    pub section: i16,
+    pub stype: u16,
+    pub storage_class: StorageClass,
+    pub aux: Vec<&'a Symbol>,
+}
+
+impl<'a> ResolvedSymbol<'a> {
+    /// The symbol's auxiliary entries, each read as its type calls for.
+    pub fn aux_entries(&self) -> Vec<AuxEntry<'a>> {
+        self.aux.iter().filter_map(|aux| AuxEntry::new(aux, self.stype)).collect()
+    }
+
+    /// The size of a function, from its auxiliary entry. Other symbols
+    /// use those bytes for something else, e.g. a section's relocation
+    /// and line number counts, so have none.
+    pub fn function_size(&self) -> Option<u32> {
+        self.aux_entries().iter()
+            .filter_map(|aux| match aux {
+                AuxEntry::Function { fsize, .. } if *fsize > 0 => Some(*fsize),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// The C type the symbol's type is built from, in the low four bits
+    /// of `stype`, e.g. "int".
+    pub fn base_type(&self) -> &'static str {
+        match self.stype & 0xf {
+            0 => "null",
+            1 => "arg",
+            2 => "char",
+            3 => "short",
+            4 => "int",
+            5 => "long",
+            6 => "float",
+            7 => "double",
+            8 => "struct",
+            9 => "union",
+            10 => "enum",
+            11 => "moe",
+            12 => "uchar",
+            13 => "ushort",
+            14 => "uint",
+            _ => "ulong",
+        }
+    }
+
+    /// The derived types applied to the base type, outermost first:
+    /// a function returning a pointer to int gives ["function",
+    /// "pointer"].
+    pub fn derived_types(&self) -> Vec<&'static str> {
+        (0..6)
+            .map(|level| (self.stype >> (4 + 2 * level)) & 3)
+            .take_while(|&derived| derived != 0)
+            .map(|derived| match derived {
+                DT_FCN => "function",
+                DT_ARY => "array",
+                _ => "pointer",
+            })
+            .collect()
+    }
 }
 
 impl SymbolTableEntry {
-    pub fn read_symbol(cursor: &mut Cursor<&[u8]>, is_aux: bool, parent_class: &StorageClass) -> io::Result<Symbol> {
+    pub fn symbol(&self) -> &Symbol {
+        &self.symbol
+    }
+
+    /// The index of the primary symbol that owns this entry, if this is
+    /// an auxiliary entry.
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn read_symbol(cursor: &mut Cursor<&[u8]>, is_aux: bool, parent_class: &StorageClass,
+                       encoding: NameEncoding) -> io::Result<Symbol> {
         let mut raw_data: [u8; 18] = [0; 18];
 
         // Consume 18 bytes.
@@ -410,7 +1004,7 @@ impl SymbolTableEntry {
 
                 let x_fname = match parent_class {
                     StorageClass::Filename => {
-                        Some(buf_to_str(&raw_data[0..14]).unwrap_or("???").to_owned())
+                        Some(decode_name(&raw_data[0..14], encoding).into_owned())
                     },
                     _ => None
                 };
@@ -504,7 +1098,21 @@ pub struct StringTable {
 }
 
 impl StringTable {
-    pub fn read(cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+    /// A table with no strings, for a file without one.
+    pub fn empty() -> Self {
+        StringTable {
+            data: vec!(0, 0, 0, 0),
+            data_size: 4,
+            strings: HashMap::new(),
+        }
+    }
+
+    pub fn read(cursor: &mut Cursor<&[u8]>) -> ReadResult<Self> {
+        StringTable::read_encoded(cursor, NameEncoding::Utf8)
+    }
+
+    /// Read the table, decoding its strings with the given encoding.
+    pub fn read_encoded(cursor: &mut Cursor<&[u8]>, encoding: NameEncoding) -> ReadResult<Self> {
         let mut data: Vec<u8> = vec!();
 
         // The first four bytes of data are ALWAYS zeroed.
@@ -518,16 +1126,21 @@ impl StringTable {
         // Denormalize the strings as we parse them.
         let mut strings = HashMap::new();
 
-        // Get the size of data we're expected to read
+        // Get the size of data we're expected to read. The size includes
+        // the four bytes of the size field itself.
         let data_size = cursor.read_u32::<BigEndian>()?;
 
+        if !in_bounds(cursor.get_ref().len(), cursor.position(), u64::from(data_size.saturating_sub(4))) {
+            return Err(CoffError::BadStrings);
+        }
+
         for j in 4..data_size as usize {
             let c = cursor.read_u8()?;
             data.push(c);
             if c == 0 {
                 // Push from the last start to here.
-                let s = buf_to_str(&data[i..j]).unwrap_or("???");
-                strings.insert(i as u32, s.to_owned());
+                let s = decode_name(&data[i..j], encoding);
+                strings.insert(i as u32, s.into_owned());
                 i = j + 1usize;
             }
         }
@@ -542,46 +1155,165 @@ impl StringTable {
     }
 
     pub fn string_at(&self, index: u32) -> Result<&str, Utf8Error> {
-        let start = index as usize;
-
-        // Index into the vector at the appropriate location, and then
-        // find the first nul.
-        let nul = self.data[start.. ].iter()
-            .position( |&c| c == b'\0')
-            .unwrap_or(self.data.len() - start);
+        // An offset past the end of the table (e.g. from a corrupt symbol)
+        // simply yields an empty name.
+        let tail = self.data.get(index as usize..).unwrap_or(&[]);
 
-        let end = start + nul;
+        // Find the first nul.
+        buf_to_str(tail)
+    }
 
-        let s = &self.data[start..end];
+    /// Like `string_at`, but escapes any bytes that aren't valid UTF-8
+    /// rather than failing.
+    pub fn string_at_lossy(&self, index: u32) -> Cow<'_, str> {
+        self.string_at_encoded(index, NameEncoding::Utf8)
+    }
 
-        str::from_utf8(&s)
+    /// The string at an offset, decoded with the given encoding.
+    pub fn string_at_encoded(&self, index: u32, encoding: NameEncoding) -> Cow<'_, str> {
+        decode_name(self.data.get(index as usize..).unwrap_or(&[]), encoding)
     }
 }
 
-pub struct Section {
+/// Version of the TSV strings format. Bump it whenever a column is
+/// added, removed, or changes meaning.
+pub const STRINGS_TSV_VERSION: u32 = 1;
+
+/// Version of the JSON symbols format. Bump it whenever a field is
+/// added, removed, or changes meaning.
+pub const SYMBOLS_JSON_VERSION: u32 = 1;
+
+/// Output formats for the strings table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StringsFormat {
+    // "[offset]    string", with a heading
+    Annotated,
+    // One string per line
+    Plain,
+    // Offset and string, separated by a tab
+    Tsv,
+}
+
+pub struct Section<'a> {
     pub header: SectionHeader,
     pub relocation_table: Vec<RelocationEntry>,
-    pub data: Vec<u8>,
+    pub line_numbers: Vec<LineNumber>,
+    /// The section's bytes. After `FileContainer::read` this borrows
+    /// from the buffer that was read; after `into_owned` it is a copy.
+    pub data: Cow<'a, [u8]>,
+}
+
+/// Which kind of range two sections share.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OverlapKind {
+    /// Their data overlaps in the file
+    FileOffset,
+    /// They are loaded at overlapping virtual addresses
+    Address,
+}
+
+/// A pair of sections whose ranges overlap.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SectionOverlap {
+    pub first: usize,
+    pub second: usize,
+    pub kind: OverlapKind,
+}
+
+impl<'a> Section<'a> {
+    ///
+    /// The range of file offsets holding the section's data. Sections
+    /// with no data in the file, such as .bss, have an empty range.
+    ///
+    pub fn file_range(&self) -> Range<u64> {
+        let start = u64::from(self.header.scnptr);
+
+        if start == 0 {
+            return 0..0;
+        }
+
+        start..start + u64::from(self.header.size)
+    }
+
+    ///
+    /// True if the section occupies memory when loaded. Dummy, copy,
+    /// comment, overlay and padding sections do not.
+    ///
+    pub fn is_allocated(&self) -> bool {
+        !self.header.section_flags().intersects(
+            SectionFlags::STYP_DSECT | SectionFlags::STYP_COPY | SectionFlags::STYP_INFO |
+            SectionFlags::STYP_OVER | SectionFlags::STYP_PAD)
+    }
+    ///
+    /// The range of virtual addresses the section occupies. A section
+    /// that would extend past the top of the address space is cut off
+    /// there.
+    ///
+    pub fn address_range(&self) -> Range<u32> {
+        self.header.vaddr..self.header.vaddr.saturating_add(self.header.size)
+    }
+
+    /// Copy the section's data, so that it no longer borrows from the
+    /// buffer it was read from.
+    pub fn into_owned(self) -> Section<'static> {
+        Section {
+            header: self.header,
+            relocation_table: self.relocation_table,
+            line_numbers: self.line_numbers,
+            data: Cow::Owned(self.data.into_owned()),
+        }
+    }
 }
 
-pub struct FileContainer {
+///
+/// A parsed COFF file. Section data is borrowed from the buffer passed
+/// to `read`, so that sections which are never looked at cost nothing
+/// to hold; `into_owned` gives a container that stands alone.
+///
+pub struct FileContainer<'a> {
     pub header: FileHeader,
     pub opt_header: Option<OptionalHeader>,
-    pub sections: Vec<Section>,
+    pub sections: Vec<Section<'a>>,
     pub symbols: Vec<SymbolTableEntry>,
     pub strings: StringTable,
+    /// The problems `read_lenient` worked around. Always empty after
+    /// `read`.
+    pub warnings: Vec<CoffError>,
+    /// How names in the symbol and string tables were decoded.
+    pub name_encoding: NameEncoding,
+    /// The file header as it appears in the file, before byte swapping.
+    pub raw_header: [u8; FILE_HEADER_SIZE as usize],
+    /// The length of the buffer the file was read from.
+    pub file_len: usize,
+    /// The SHA-256 digest of the buffer the file was read from.
+    #[cfg(feature = "hash")]
+    pub sha256: [u8; 32],
+}
+
+/// How `FileContainer::read_with` reads a file.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ReadOptions {
+    /// Work around damage to the file, as `read_lenient` does.
+    pub lenient: bool,
+    /// How to decode symbol and file names. Section names are always
+    /// read as UTF-8, since the link editor only uses ASCII ones.
+    pub name_encoding: NameEncoding,
 }
 
-impl FileContainer {
+impl<'a> FileContainer<'a> {
     ///
     /// Read in and destructure a WE32100 COFF file.
     ///
-
     fn bad_metadata(header: &FileHeader) -> bool {
         !(header.magic == MAGIC_WE32K || header.magic == MAGIC_WE32K_TV)
     }
 
-    fn read_sections(file_header: &FileHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Vec<Section>> {
+    fn read_sections(file_header: &FileHeader, cursor: &mut Cursor<&'a [u8]>, lenient: bool,
+                     warnings: &mut Vec<CoffError>) -> ReadResult<Vec<Section<'a>>> {
+        let buf_len = cursor.get_ref().len();
+
+        // The number of bytes in the file from an offset to the end.
+        let available = |offset: u32| (buf_len as u64).saturating_sub(u64::from(offset));
         let mut section_headers: Vec<SectionHeader> = vec!();
 
         // Read the section headers
@@ -594,13 +1326,74 @@ impl FileContainer {
 
         for header in section_headers {
             let mut relocation_table: Vec<RelocationEntry> = vec!();
-            let mut data: Vec<u8> = vec!();
+            let mut data: &[u8] = &[];
+
+            // Sections such as .bss occupy memory but have no data in
+            // the file, which is indicated by a zero data offset.
+            let has_data = header.scnptr != 0 && header.size > 0;
+
+            // Both the relocation table and the section data must lie
+            // entirely within the file. In lenient mode, whatever part
+            // of them is there is kept instead.
+            let reloc_len = u64::from(header.nreloc) * 10;
+            let lnno_len = u64::from(header.nlnno) * 6;
+            let mut nreloc = u64::from(header.nreloc);
+            let mut nlnno = u64::from(header.nlnno);
+            let mut size = u64::from(header.size);
+
+            if !in_bounds(buf_len, u64::from(header.relptr), reloc_len) {
+                let error = CoffError::SectionOutOfBounds {
+                    section: header.name().into_owned(),
+                    what: "relocation table",
+                    offset: header.relptr,
+                    len: reloc_len,
+                };
+
+                if !lenient {
+                    return Err(error);
+                }
+
+                warnings.push(error);
+                nreloc = available(header.relptr) / 10;
+            }
+
+            if !in_bounds(buf_len, u64::from(header.lnnoptr), lnno_len) {
+                let error = CoffError::SectionOutOfBounds {
+                    section: header.name().into_owned(),
+                    what: "line number table",
+                    offset: header.lnnoptr,
+                    len: lnno_len,
+                };
+
+                if !lenient {
+                    return Err(error);
+                }
+
+                warnings.push(error);
+                nlnno = available(header.lnnoptr) / 6;
+            }
+
+            if has_data && !in_bounds(buf_len, u64::from(header.scnptr), u64::from(header.size)) {
+                let error = CoffError::SectionOutOfBounds {
+                    section: header.name().into_owned(),
+                    what: "data",
+                    offset: header.scnptr,
+                    len: u64::from(header.size),
+                };
+
+                if !lenient {
+                    return Err(error);
+                }
+
+                warnings.push(error);
+                size = available(header.scnptr);
+            }
 
             // Get relocation information
-            if header.nreloc > 0 {
+            if nreloc > 0 {
                 cursor.seek(SeekFrom::Start(u64::from(header.relptr)))?;
 
-                for _ in 0..header.nreloc {
+                for _ in 0..nreloc {
                     let entry = RelocationEntry {
                         vaddr: cursor.read_u32::<BigEndian>()?,
                         symndx: cursor.read_u32::<BigEndian>()?,
@@ -610,20 +1403,35 @@ impl FileContainer {
                 }
             }
 
-            // Get data
-            if header.size > 0 {
-                cursor.seek(SeekFrom::Start(u64::from(header.scnptr)))?;
+            // Get line numbers
+            let mut line_numbers: Vec<LineNumber> = vec!();
+
+            if nlnno > 0 {
+                cursor.seek(SeekFrom::Start(u64::from(header.lnnoptr)))?;
 
-                for _ in 0..header.size {
-                    data.push(cursor.read_u8()?);
+                for _ in 0..nlnno {
+                    line_numbers.push(LineNumber {
+                        addr: cursor.read_u32::<BigEndian>()?,
+                        lnno: cursor.read_u16::<BigEndian>()?,
+                    });
                 }
             }
 
+            // Get data
+            // This borrows from the buffer rather than copying. In
+            // lenient mode the data may start past the end of the
+            // file, leaving nothing.
+            if has_data {
+                let rest = cursor.get_ref().get(header.scnptr as usize..).unwrap_or(&[]);
+                data = &rest[..size as usize];
+            }
+
             // Done with this section.
             let section = Section {
                 header,
                 relocation_table,
-                data,
+                line_numbers,
+                data: Cow::Borrowed(data),
             };
 
             sections.push(section);
@@ -632,48 +1440,43 @@ impl FileContainer {
         Ok(sections)
     }
 
-    fn read_symbol_table(header: &FileHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Vec<SymbolTableEntry>> {
+    fn read_symbol_table(header: &FileHeader, cursor: &mut Cursor<&[u8]>,
+                         encoding: NameEncoding) -> ReadResult<Vec<SymbolTableEntry>> {
         let mut symbols: Vec<SymbolTableEntry> = vec!();
 
         if header.symbol_count > 0 {
+            let table_len = u64::from(header.symbol_count) * 18;
+
+            if !in_bounds(cursor.get_ref().len(), u64::from(header.symbol_table_offset), table_len) {
+                return Err(CoffError::BadSymbols);
+            }
+
             cursor.seek(SeekFrom::Start(u64::from(header.symbol_table_offset)))?;
 
-            // Keep track of which symbols are aux symbols.
-            let mut is_aux = false;
-            let mut aux_index: u8 = 0;
-            let mut sclass: StorageClass = StorageClass::Null;
+            let count = header.symbol_count as usize;
 
-            for _ in 0..header.symbol_count {
-                let symbol = SymbolTableEntry::read_symbol(cursor, is_aux, &sclass)?;
+            while symbols.len() < count {
+                let symbol = SymbolTableEntry::read_symbol(cursor, false, &StorageClass::Null, encoding)?;
 
-                if is_aux {
-                    aux_index -= 1;
-                    if aux_index == 0 {
-                        is_aux = false;
-                    }
-                }
+                let (n_numaux, storage_class) = match symbol {
+                    Symbol::Primary { n_numaux, storage_class, .. } => (n_numaux, storage_class),
+                    Symbol::Auxiliary { .. } => (0, StorageClass::Null),
+                };
 
-                match symbol {
-                    Symbol::Primary {
-                        n_name: _,
-                        n_zeroes: _,
-                        n_offset: _,
-                        n_value: _,
-                        n_scnum: _,
-                        n_type: _,
-                        n_numaux,
-                        storage_class,
-                    } => {
-                        if n_numaux > 0 {
-                            is_aux = true;
-                            aux_index = n_numaux;
-                            sclass = storage_class;
-                        }
-                    },
-                    _ => {}
+                // A primary symbol may not claim more auxiliary entries
+                // than remain in the table.
+                let parent = symbols.len();
+
+                if parent + 1 + n_numaux as usize > count {
+                    return Err(CoffError::BadSymbols);
                 }
 
-                symbols.push(SymbolTableEntry { symbol });
+                symbols.push(SymbolTableEntry { symbol, parent: None });
+
+                for _ in 0..n_numaux {
+                    let symbol = SymbolTableEntry::read_symbol(cursor, true, &storage_class, encoding)?;
+                    symbols.push(SymbolTableEntry { symbol, parent: Some(parent) });
+                }
             }
         }
 
@@ -683,169 +1486,244 @@ impl FileContainer {
     ///
     /// Consume the buffer
     ///
-    pub fn read(buf: &[u8]) -> ReadResult<Self> {
-        let mut cursor = Cursor::new(buf);
+    pub fn read(buf: &'a [u8]) -> ReadResult<Self> {
+        FileContainer::read_with(buf, &ReadOptions::default())
+    }
 
-        // Read the file header.
-        let header = match FileHeader::read(&mut cursor) {
-            Ok(h) => {
-                if FileContainer::bad_metadata(&h) {
-                    return Err(CoffError::BadFileHeader)
-                } else {
-                    h
-                }
-            },
-            Err(_) => return Err(CoffError::BadFileHeader)
+    ///
+    /// Read as much of a damaged or truncated file as possible. A
+    /// section whose data or relocation table runs past the end of the
+    /// file keeps the part that is there, and a symbol or string table
+    /// that can't be read is left empty. Each such problem is recorded
+    /// in `warnings` instead of failing the whole read. The file and
+    /// section headers must still be intact.
+    ///
+    pub fn read_lenient(buf: &'a [u8]) -> ReadResult<Self> {
+        let opts = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
         };
 
-        // If an optional header is indicated in the file header, read
-        // it.
-        let opt_header = if header.opt_header > 0 {
-            match OptionalHeader::read(&mut cursor) {
-                Ok(h) => Some(h),
-                Err(_) => return Err(CoffError::BadOptionalHeader)
-            }
-        } else {
-            None
-        };
+        FileContainer::read_with(buf, &opts)
+    }
 
-        // Now we have to seek to the sections area.
-        if let Err(_) = cursor.seek(SeekFrom::Start(u64::from(FILE_HEADER_SIZE + header.opt_header))) {
-            return Err(CoffError::BadSections)
+    ///
+    /// Copy the file header's bytes out of a buffer that `FileHeader::read`
+    /// has already read a header from.
+    ///
+    fn raw_header(buf: &[u8]) -> [u8; FILE_HEADER_SIZE as usize] {
+        let mut raw = [0; FILE_HEADER_SIZE as usize];
+        raw.copy_from_slice(&buf[..FILE_HEADER_SIZE as usize]);
+        raw
+    }
+
+    ///
+    /// Read a file as `opts` says: leniently or not, and with names in
+    /// a given encoding.
+    ///
+    pub fn read_with(buf: &'a [u8], opts: &ReadOptions) -> ReadResult<Self> {
+        let lenient = opts.lenient;
+        let mut cursor = Cursor::new(buf);
+        let mut warnings = vec!();
+
+        // Read the file header.
+        let header = FileHeader::read(&mut cursor)?;
+
+        if FileContainer::bad_metadata(&header) {
+            return Err(CoffError::BadFileHeader)
         }
 
-        // Read sections
-        let sections = match FileContainer::read_sections(&header, &mut cursor) {
-            Ok(s) => s,
-            Err(_) => return Err(CoffError::BadSections)
+        // If an optional header is indicated in the file header, read
+        // it.
+        let opt_header = if header.opt_header > 0 {
+            Some(OptionalHeader::read(&mut cursor)?)
+        } else {
+            None
         };
 
+        // Now we have to seek to the sections area.
+        let sections_offset = u64::from(FILE_HEADER_SIZE) + u64::from(header.opt_header);
+        cursor.seek(SeekFrom::Start(sections_offset))?;
+
+        // Read sections
+        let sections = FileContainer::read_sections(&header, &mut cursor, lenient, &mut warnings)?;
+
         // Load symbols
-        let symbols = match FileContainer::read_symbol_table(&header, &mut cursor) {
-            Ok(s) => s,
-            Err(_) => return Err(CoffError::BadSymbols)
+        let symbols = match FileContainer::read_symbol_table(&header, &mut cursor, opts.name_encoding) {
+            Ok(symbols) => Some(symbols),
+            Err(e) if lenient => {
+                warnings.push(e);
+                None
+            }
+            Err(e) => return Err(e),
         };
 
-        // The cursor is now at the correct position to read string entries.
-        let strings = match StringTable::read(&mut cursor) {
-            Ok(s) => s,
-            Err(_) => return Err(CoffError::BadStrings)
+        // The cursor is now at the correct position to read string
+        // entries, unless the symbols couldn't be read. The string
+        // table follows the symbol table, so a file without symbols,
+        // which may end right after its headers, has none.
+        let strings = match symbols {
+            Some(_) if header.symbol_count == 0 => StringTable::empty(),
+            Some(_) => match StringTable::read_encoded(&mut cursor, opts.name_encoding) {
+                Ok(strings) => strings,
+                Err(e) if lenient => {
+                    warnings.push(e);
+                    StringTable::empty()
+                }
+                Err(e) => return Err(e),
+            },
+            None => StringTable::empty(),
         };
 
         let container = FileContainer {
             header,
             opt_header,
             sections,
-            symbols,
+            symbols: symbols.unwrap_or_default(),
             strings,
+            warnings,
+            name_encoding: opts.name_encoding,
+            raw_header: FileContainer::raw_header(buf),
+            file_len: buf.len(),
+            #[cfg(feature = "hash")]
+            sha256: Sha256::digest(buf).into(),
         };
 
         Ok(container)
     }
 
     ///
-    /// Dump relocation table from the specified section to stdout.
+    /// Read a COFF file embedded in a larger buffer, such as a disk
+    /// image, starting at `base`. All file offsets in the headers are
+    /// taken as relative to `base`.
     ///
+    pub fn read_at(buf: &'a [u8], base: usize) -> ReadResult<Self> {
+        match buf.get(base..) {
+            Some(object) => FileContainer::read(object),
+            None => Err(CoffError::BadFileHeader),
+        }
+    }
 
-    pub fn dump_relocation_table(&self, sec_num: usize) -> Result<(), OffsetError> {
-        if self.sections.len() == 0 || sec_num > (self.sections.len() - 1) {
-            return Err(OffsetError)
+    ///
+    /// Read a COFF file from any seekable source. The source is read
+    /// from its beginning, regardless of its current position.
+    ///
+    pub fn from_reader<R: Read + Seek>(mut reader: R) -> ReadResult<FileContainer<'static>> {
+        let mut buf = Vec::new();
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_to_end(&mut buf)?;
+        Ok(FileContainer::read(&buf)?.into_owned())
+    }
+
+    ///
+    /// Copy the data of every section, giving a container that no
+    /// longer borrows from the buffer it was read from.
+    ///
+    pub fn into_owned(self) -> FileContainer<'static> {
+        FileContainer {
+            header: self.header,
+            opt_header: self.opt_header,
+            sections: self.sections.into_iter().map(Section::into_owned).collect(),
+            symbols: self.symbols,
+            strings: self.strings,
+            warnings: self.warnings,
+            name_encoding: self.name_encoding,
+            raw_header: self.raw_header,
+            file_len: self.file_len,
+            #[cfg(feature = "hash")]
+            sha256: self.sha256,
+        }
+    }
+
+    ///
+    /// The SHA-256 digest of the file, as 64 lowercase hex digits.
+    ///
+    #[cfg(feature = "hash")]
+    pub fn sha256_hex(&self) -> String {
+        self.sha256.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    ///
+    /// Print a one-line summary of each section to stdout.
+    ///
+    pub fn list_sections(&self) {
+        if self.sections.is_empty() {
+            println!("No sections.");
+            return;
         }
 
-        let section = &self.sections[sec_num];
+        println!("{:>3}  {:<8}  {:<10}  {:<10}  {:<10}  {:>6}  {:>6}  Flags",
+                 "Idx", "Name", "VAddr", "Size", "Offset", "Relocs", "Lines");
+
+        for (i, section) in self.sections.iter().enumerate() {
+            let header = &section.header;
+
+            println!("{:>3}  {:<8}  0x{:08x}  0x{:08x}  0x{:08x}  {:>6}  {:>6}  {}",
+                     i, header.name(), header.vaddr, header.size, header.scnptr,
+                     header.nreloc, header.nlnno, header.section_flags().names());
+        }
+    }
+
+    ///
+    /// Dump relocation table from the specified section to stdout.
+    ///
+    pub fn dump_relocation_table(&self, sec_num: usize) -> Result<(), OffsetError> {
+        let section = match self.sections.get(sec_num) {
+            Some(s) => s,
+            None => return Err(OffsetError)
+        };
 
         println!("    Relocation Table:");
 
         // If there is relocation data, let's dump that too.
-        if section.relocation_table.len() > 0 {
-            println!("        Num    Vaddr       Symndx  Type");
-            println!("        -----  ----------  ------  ----");
+        if !section.relocation_table.is_empty() {
+            println!("        Num    Vaddr       Symndx  Type  Effect");
+            println!("        -----  ----------  ------  ----  ------");
             for (i, entry) in section.relocation_table.iter().enumerate() {
-                println!("        [{:03}]  0x{:08x}  {:6}  {:3}",
-                         i,  entry.vaddr, entry.symndx, entry.rtype);
+                let name = match self.symbol_name_at(entry.symndx as usize) {
+                    Some(name) => format!("`{}`", name),
+                    None if entry.symndx as usize >= self.symbols.len() => {
+                        format!("symbol {} (out of range)", entry.symndx)
+                    }
+                    None => format!("symbol {} (an auxiliary entry)", entry.symndx),
+                };
+
+                let effect = match entry.type_info() {
+                    Some(("R_ABS", desc)) => desc.to_owned(),
+                    Some((rname, desc)) => format!("{} {} {}", rname, desc, name),
+                    None => format!("unknown type, referencing {}", name),
+                };
+
+                println!("        [{:03}]  0x{:08x}  {:6}  {:4}  {}",
+                         i,  entry.vaddr, entry.symndx, entry.rtype, effect);
             }
         } else {
             println!("       No Entries.")
         }
 
-        return Ok(())
+        Ok(())
     }
 
     ///
     /// Dump section data from the specified section to stdout.
     ///
     pub fn dump_section_data(&self, sec_num: usize) -> Result<(), OffsetError> {
-        if self.sections.len() == 0 || sec_num > (self.sections.len() - 1) {
-            return Err(OffsetError)
-        }
-
-        let section = &self.sections[sec_num];
+        let section = match self.sections.get(sec_num) {
+            Some(s) => s,
+            None => return Err(OffsetError)
+        };
         let header = &section.header;
-        let sec_name = buf_to_str(&header.name).unwrap_or("???");
+        let sec_name = header.name();
 
         println!("    Section Data (number {}, name {}):", sec_num, sec_name);
 
-        if section.data.len() == 0 {
+        if section.data.is_empty() {
             println!("        No Data.");
             return Ok(())
         }
 
-        // Make a cute little array for our read data.
-        let mut row_bytes: [u8; 16] = [0; 16];
-        let end = section.data.len() - 1;
-
-        for (i, b) in section.data.iter().enumerate() {
-            row_bytes[i % 16] = *b;
-
-            if i % 16 == 0 {
-                let vaddr = header.vaddr + i as u32;
-                print!("        {:08x}:   ", vaddr);
-            }
-
-            print!("{:02x} ", b);
-
-            if (i + 1) % 8 == 0 && (i + 1) % 16 != 0 {
-                print!("  ");
-            }
-
-            // If we need to end a line, it's time to print the
-            // human-readable summary.
-
-            if (i + 1) % 16 == 0 || i == end {
-
-                // How many empty characters do we need to pad out
-                // before the summary?
-                let spaces = if i == end {
-                    15 - (end % 16)
-                } else {
-                    0
-                };
-
-                for _ in 0..spaces {
-                    print!("   ");
-                }
-
-                if spaces > 8 {
-                    print!("  ");
-                }
-
-                print!("  | ");
-
-                for (x, c) in row_bytes.iter().enumerate() {
-                    if x < (16 - spaces) as usize {
-                        let printable = if *c >= 0x20 && *c < 0x7f {
-                            *c as char
-                        } else {
-                            b'.' as char
-                        };
-                        print!("{}", printable);
-                    } else {
-                        print!(" ");
-                    }
-                }
-
-                println!(" |");
-            }
+        for (row, bytes) in section.data.chunks(16).enumerate() {
+            println!("{}", data_row(header.vaddr.wrapping_add(row as u32 * 16), bytes));
         }
 
         Ok(())
@@ -878,52 +1756,63 @@ impl FileContainer {
                     n_numaux,
                     storage_class,
                 } => {
-                    let name = if *n_zeroes == 0 {
-                        self.strings.string_at(*n_offset).unwrap_or("???")
-                    } else {
-                        buf_to_str(n_name).unwrap_or("???")
-                    };
+                    let name = self.symbol_name(n_name, *n_zeroes, *n_offset);
 
                     println!("    {{");
                     println!("        index: {},", i);
                     println!("        name: '{}',", name);
                     println!("        value: '0x{:x}',", n_value);
-                    println!("        section: {},", n_scnum);
+                    println!("        section: '{}',", self.section_label(*n_scnum));
                     println!("        type: '0x{:02x}',", n_type);
                     println!("        class: '{:?}',", storage_class);
                     println!("        numaux: {}", n_numaux);
 
                 },
-                Symbol::Auxiliary {
-                    x_fname,
-                    x_tagndx,
-                    x_lnno,
-                    x_size,
-                    x_fsize,
-                    x_lnnoptr,
-                    x_endndx,
-                    x_dimen,
-                    x_tvndx,
-                } => {
+                Symbol::Auxiliary { .. } => {
+                    // The record is a union whose meaning depends on
+                    // the type of the symbol it belongs to.
+                    let n_type = match e.parent.map(|p| &self.symbols[p].symbol) {
+                        Some(Symbol::Primary { n_type, .. }) => *n_type,
+                        _ => 0,
+                    };
+
                     println!("    {{");
                     println!("        index: {},", i);
-                    if x_fname.is_some() {
-                        println!("        filename: '{}',", x_fname.as_ref().unwrap());
-                    } else {
-                        println!("        tagindex: {},", x_tagndx);
-                        println!("        lnno: '0x{:x}',", x_lnno);
-                        println!("        size: '0x{:x}',", x_size);
-                        println!("        fsize: '0x{:x}',", x_fsize);
+
+                    match AuxEntry::new(symbol, n_type) {
+                        Some(AuxEntry::File { name }) => {
+                            println!("        filename: '{}',", name);
+                        }
+                        Some(AuxEntry::Function { tagndx, fsize, lnnoptr, endndx, tvndx }) => {
+                            println!("        tagindex: {},", tagndx);
+                            println!("        fsize: '0x{:x}',", fsize);
+                            println!("        lnnoptr: '0x{:x}',", lnnoptr);
+                            println!("        endndx: {},", endndx);
+                            println!("        tvndx: {}", tvndx);
+                        }
+                        Some(AuxEntry::Array { tagndx, lnno, size, dimen, tvndx }) => {
+                            println!("        tagindex: {},", tagndx);
+                            println!("        lnno: '0x{:x}',", lnno);
+                            println!("        size: '0x{:x}',", size);
+                            for (d, dim) in dimen.iter().enumerate() {
+                                println!("        dim{}: {},", d, dim);
+                            }
+                            println!("        tvndx: {}", tvndx);
+                        }
+                        Some(AuxEntry::Other { tagndx, lnno, size, lnnoptr, endndx, tvndx }) => {
+                            println!("        tagindex: {},", tagndx);
+                            println!("        lnno: '0x{:x}',", lnno);
+                            println!("        size: '0x{:x}',", size);
+                            println!("        lnnoptr: '0x{:x}',", lnnoptr);
+                            println!("        endndx: {},", endndx);
+                            println!("        tvndx: {}", tvndx);
+                        }
+                        None => {}
                     }
-                    println!("        lnnoptr: '0x{:x}',", x_lnnoptr);
-                    println!("        endndx: {},", x_endndx);
-                    println!("        dim0: {},", x_dimen[0]);
-                    println!("        dim1: {},", x_dimen[1]);
-                    println!("        tvndx: {}", x_tvndx);
                 }
             }
 
-            if i < self.symbols.len() - 1 {
+            if i + 1 < self.symbols.len() {
                 println!("    }},")
             } else {
                 println!("    }}");
@@ -933,31 +1822,884 @@ impl FileContainer {
         println!("]");
     }
 
+    ///
+    /// Dump the symbol table to stdout as JSON, one object per line.
+    ///
+    /// The first line is a header, e.g.
+    /// `{"schema":"we32dis-symbols","version":1,"tool_version":"0.1.0"}`.
+    /// Each primary symbol follows as an object with the fields:
+    ///
+    ///   - `index`: the symbol's index in the symbol table
+    ///   - `name`: the name, from the entry or the string table
+    ///   - `value`: n_value, usually an address
+    ///   - `section`: the section name, or "UNDEF", "ABS" or "DEBUG"
+    ///   - `section_number`: n_scnum
+    ///   - `class`: the storage class as named in the C headers, e.g.
+    ///     "C_EXT"
+    ///   - `type`: an object with `raw` (n_type), `base` (e.g. "int")
+    ///     and `derived` (e.g. ["function", "pointer"], outermost first)
+    ///   - `aux`: an array of the auxiliary entries, in order
+    ///
+    /// An auxiliary entry is read according to the symbol it belongs
+    /// to. Each has a `kind` and the fields that kind uses:
+    ///
+    ///   - "file": `filename`
+    ///   - "function": `tagindex`, `fsize`, `lnnoptr`, `endndx`, `tvndx`
+    ///   - "array": `tagindex`, `lnno`, `size`, `dimensions` (four
+    ///     numbers), `tvndx`
+    ///   - "other": `tagindex`, `lnno`, `size`, `lnnoptr`, `endndx`,
+    ///     `tvndx`
+    ///
+    pub fn dump_symbols_json(&self) {
+        println!("{{\"schema\":\"we32dis-symbols\",\"version\":{},\"tool_version\":\"{}\"}}",
+                 SYMBOLS_JSON_VERSION, json_escape(crate::VERSION));
+
+        for sym in self.resolved_symbols() {
+            let derived: Vec<String> = sym.derived_types().iter()
+                .map(|d| format!("\"{}\"", d))
+                .collect();
+
+            let aux: Vec<String> = sym.aux_entries().iter().map(|aux| match aux {
+                AuxEntry::File { name } => {
+                    format!("{{\"kind\":\"file\",\"filename\":\"{}\"}}", json_escape(name))
+                }
+                AuxEntry::Function { tagndx, fsize, lnnoptr, endndx, tvndx } => {
+                    format!("{{\"kind\":\"function\",\"tagindex\":{},\"fsize\":{},\"lnnoptr\":{},\"endndx\":{},\"tvndx\":{}}}",
+                            tagndx, fsize, lnnoptr, endndx, tvndx)
+                }
+                AuxEntry::Array { tagndx, lnno, size, dimen, tvndx } => {
+                    format!("{{\"kind\":\"array\",\"tagindex\":{},\"lnno\":{},\"size\":{},\"dimensions\":[{},{},{},{}],\"tvndx\":{}}}",
+                            tagndx, lnno, size, dimen[0], dimen[1], dimen[2], dimen[3], tvndx)
+                }
+                AuxEntry::Other { tagndx, lnno, size, lnnoptr, endndx, tvndx } => {
+                    format!("{{\"kind\":\"other\",\"tagindex\":{},\"lnno\":{},\"size\":{},\"lnnoptr\":{},\"endndx\":{},\"tvndx\":{}}}",
+                            tagndx, lnno, size, lnnoptr, endndx, tvndx)
+                }
+            }).collect();
+
+            println!("{{\"index\":{},\"name\":\"{}\",\"value\":{},\"section\":\"{}\",\"section_number\":{},\"class\":\"{}\",\"type\":{{\"raw\":{},\"base\":\"{}\",\"derived\":[{}]}},\"aux\":[{}]}}",
+                     sym.index, json_escape(&sym.name), sym.value, json_escape(&self.section_label(sym.section)),
+                     sym.section, sym.storage_class.name(), sym.stype, sym.base_type(), derived.join(","),
+                     aux.join(","));
+        }
+    }
+
     pub fn dump_strings_table(&self) {
-        println!("Strings Table:");
+        self.dump_strings(StringsFormat::Annotated);
+    }
 
+    ///
+    /// Dump the strings table to stdout in the given format, ordered by
+    /// offset. The TSV format starts with a comment line giving its
+    /// version, as in "# we32dis-strings version=1 tool_version=0.1.0".
+    ///
+    pub fn dump_strings(&self, format: StringsFormat) {
         let strings = &self.strings;
 
-        if strings.strings.len() > 0 {
-            // Strings are kept in an unsorted hash map, so they should
-            // be sorted before printing out.
-            let mut keys: Vec<&u32> = strings.strings.keys().collect();
-            keys.sort();
-            for key in keys.iter() {
-                if let Some(val) = &strings.strings.get(key) {
-                    println!("    [{:4}]    {}", key, val);
+        if format == StringsFormat::Tsv {
+            println!("# we32dis-strings version={} tool_version={}", STRINGS_TSV_VERSION, crate::VERSION);
+        }
+
+        if format == StringsFormat::Annotated {
+            println!("Strings Table:");
+
+            if strings.strings.is_empty() {
+                println!("    No Strings");
+                return;
+            }
+        }
+
+        // Strings are kept in an unsorted hash map, so they should
+        // be sorted before printing out.
+        let mut keys: Vec<&u32> = strings.strings.keys().collect();
+        keys.sort();
+        for key in keys.iter() {
+            if let Some(val) = &strings.strings.get(key) {
+                match format {
+                    StringsFormat::Annotated => println!("    [{:4}]    {}", key, val),
+                    StringsFormat::Plain => println!("{}", val),
+                    StringsFormat::Tsv => println!("{}\t{}", key, val),
                 }
             }
+        }
+    }
+
+    ///
+    /// Print the runs of printable ASCII in a section's data, or in
+    /// every section's if `name` is `None`, with their virtual
+    /// addresses.
+    ///
+    pub fn dump_ascii_strings(&self, name: Option<&str>, min_len: usize) -> Result<(), OffsetError> {
+        if let Some(name) = name {
+            if !self.sections.iter().any(|s| s.header.name() == name) {
+                return Err(OffsetError);
+            }
+        }
+
+        for section in &self.sections {
+            match name {
+                Some(name) if section.header.name() != name => continue,
+                _ => {}
+            }
+
+            for (offset, text) in ascii_strings(&section.data, min_len) {
+                println!("0x{:08x}: {:?}", section.header.vaddr.wrapping_add(offset as u32), text);
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Locate the entry point named in the optional header, returning
+    /// the number of the text section containing it and its offset
+    /// into that section's data.
+    ///
+    pub fn entry_point(&self) -> Option<(usize, usize)> {
+        let entry = self.opt_header.as_ref()?.entry_point;
+
+        self.code_sections()
+            .find(|(_, s)| s.address_range().contains(&entry))
+            .map(|(i, s)| (i, (entry - s.header.vaddr) as usize))
+    }
+
+    ///
+    /// Resolve a primary symbol's name, either inline or from the string table.
+    ///
+    /// A name is in the string table only when the first four bytes of
+    /// the name field are zero, and then the last four are its offset.
+    /// An inline name can't start with a NUL, since that is the empty
+    /// name, so the two cases never overlap. An offset into the
+    /// table's four-byte size field is not a string at all; such a
+    /// name is empty, just as the inline reading of the field would be.
+    ///
+    fn symbol_name<'s>(&'s self, n_name: &'s [u8], n_zeroes: u32, n_offset: u32) -> Cow<'s, str> {
+        if n_zeroes == 0 && n_offset >= 4 {
+            self.strings.string_at_encoded(n_offset, self.name_encoding)
         } else {
-            println!("    No Strings");
+            decode_name(n_name, self.name_encoding)
+        }
+    }
+
+    ///
+    /// The name of the primary symbol at `index` in the symbol table,
+    /// as a relocation entry refers to it. Returns `None` if the index
+    /// is past the end of the table or is an auxiliary entry.
+    ///
+    pub fn symbol_name_at(&self, index: usize) -> Option<Cow<'_, str>> {
+        match &self.symbols.get(index)?.symbol {
+            Symbol::Primary { n_name, n_zeroes, n_offset, .. } => {
+                Some(self.symbol_name(n_name, *n_zeroes, *n_offset))
+            }
+            Symbol::Auxiliary { .. } => None,
+        }
+    }
+
+    ///
+    /// Return every primary symbol, with its name resolved and its
+    /// auxiliary entries attached.
+    ///
+    pub fn resolved_symbols(&self) -> Vec<ResolvedSymbol<'_>> {
+        let mut resolved: Vec<ResolvedSymbol> = vec!();
+
+        for (index, entry) in self.symbols.iter().enumerate() {
+            match &entry.symbol {
+                Symbol::Primary { n_name, n_zeroes, n_offset, n_value, n_scnum, n_type, storage_class, .. } => {
+                    resolved.push(ResolvedSymbol {
+                        index,
+                        name: self.symbol_name(n_name, *n_zeroes, *n_offset).into_owned(),
+                        value: *n_value,
+                        section: *n_scnum,
+                        stype: *n_type,
+                        storage_class: *storage_class,
+                        aux: vec!(),
+                    });
+                },
+                Symbol::Auxiliary { .. } => {
+                    if let Some(parent) = resolved.last_mut() {
+                        if Some(parent.index) == entry.parent {
+                            parent.aux.push(&entry.symbol);
+                        }
+                    }
+                }
+            }
         }
+
+        resolved
     }
 
-    pub fn section_data(&self, sec_num: usize) -> Option<&Vec<u8>> {
+    ///
+    /// Describe a symbol's section number: either the name of the
+    /// section, or one of the special UNDEF, ABS or DEBUG labels.
+    ///
+    pub fn section_label(&self, scnum: i16) -> String {
+        match scnum {
+            N_UNDEF => String::from("UNDEF"),
+            N_ABS => String::from("ABS"),
+            N_DEBUG => String::from("DEBUG"),
+            n if n > 0 => match self.sections.get(n as usize - 1) {
+                Some(section) => section.header.name().into_owned(),
+                None => format!("{} (invalid)", n),
+            },
+            n => format!("{} (invalid)", n),
+        }
+    }
+
+    ///
+    /// Summarize the file on one line, e.g. "WE32000 executable, 3
+    /// sections, entry 0x80800000, 142 symbols, MAU required".
+    ///
+    /// The fields always appear in this order, separated by ", ":
+    ///
+    ///   - the machine ("WE32000" or "unknown machine") and either
+    ///     "executable" or "object"
+    ///   - the number of sections
+    ///   - "entry 0x..." with the entry point, if there is an optional
+    ///     header
+    ///   - the number of symbol table entries
+    ///   - "32100 required" and "MAU required", if the flags say so
+    ///
+    pub fn summary(&self) -> String {
+        let header = &self.header;
+
+        let machine = match header.magic {
+            MAGIC_WE32K | MAGIC_WE32K_TV => "WE32000",
+            _ => "unknown machine",
+        };
+
+        let mut fields = vec![
+            format!("{} {}", machine, if header.executable() { "executable" } else { "object" }),
+            format!("{} section{}", header.section_count, if header.section_count == 1 { "" } else { "s" }),
+        ];
+
+        if let Some(opt_header) = &self.opt_header {
+            fields.push(format!("entry 0x{:08x}", opt_header.entry_point));
+        }
+
+        fields.push(format!("{} symbol{}", header.symbol_count, if header.symbol_count == 1 { "" } else { "s" }));
+
+        if header.is_32100_required() {
+            fields.push(String::from("32100 required"));
+        }
+
+        if header.mau_required() {
+            fields.push(String::from("MAU required"));
+        }
+
+        fields.join(", ")
+    }
+
+    ///
+    /// Identify the file by content, for cataloguing: its length in
+    /// bytes, its file header as hex, and, with the "hash" feature,
+    /// its SHA-256 digest, e.g.
+    /// "1234 bytes, header 01700003..., sha256 9f86d081...".
+    ///
+    pub fn identity(&self) -> String {
+        let header: String = self.raw_header.iter().map(|b| format!("{:02x}", b)).collect();
+        let identity = format!("{} bytes, header {}", self.file_len, header);
+
+        #[cfg(feature = "hash")]
+        let identity = format!("{}, sha256 {}", identity, self.sha256_hex());
+
+        identity
+    }
+
+    ///
+    /// Print a compact, address-sorted map of the defined symbols to
+    /// stdout: one line per symbol with its address, section, size
+    /// (from a function's auxiliary entry, or "-" for other symbols
+    /// and functions without one), and name.
+    ///
+    pub fn dump_map(&self) {
+        let mut symbols: Vec<ResolvedSymbol> = self.resolved_symbols().into_iter()
+            .filter(|sym| sym.section > 0 || sym.section == N_ABS)
+            .filter(|sym| matches!(sym.storage_class,
+                                   StorageClass::ExternalSym | StorageClass::ExternalDef |
+                                   StorageClass::Static | StorageClass::Label))
+            .collect();
+
+        symbols.sort_by(|a, b| a.value.cmp(&b.value).then_with(|| a.name.cmp(&b.name)));
+
+        for sym in symbols {
+            let size = match sym.function_size() {
+                Some(size) => format!("{:08x}", size),
+                None => String::from("-"),
+            };
+
+            println!("{:08x}  {:<8}  {:>8}  {}", sym.value, self.section_label(sym.section), size, sym.name);
+        }
+    }
+
+    ///
+    /// Build a map of addresses to the names of the symbols defined
+    /// in this file's sections.
+    ///
+    pub fn symbol_map(&self) -> SymbolMap {
+        let mut map = SymbolMap::new();
+
+        for sym in self.resolved_symbols() {
+            if sym.section <= 0 {
+                continue;
+            }
+
+            match sym.storage_class {
+                StorageClass::ExternalSym |
+                StorageClass::ExternalDef |
+                StorageClass::Static |
+                StorageClass::Label => map.insert(sym.value, &sym.name),
+                _ => {}
+            }
+        }
+
+        map
+    }
+
+    ///
+    /// Join a section's line number table with the symbol table to find
+    /// the address, file and absolute line number of each source line,
+    /// in table order. The file is the one named by the last ".file"
+    /// symbol before the function. Entries for a function that isn't
+    /// in the symbol table are skipped.
+    ///
+    pub fn source_lines(&self, sec_num: usize) -> Vec<SourceLine> {
+        let mut lines = vec!();
+
+        let section = match self.sections.get(sec_num) {
+            Some(section) => section,
+            None => return lines,
+        };
+
+        let symbols = self.resolved_symbols();
+        let mut function: Option<(Option<String>, u32)> = None;
+
+        for entry in &section.line_numbers {
+            if entry.lnno == 0 {
+                let index = entry.addr as usize;
+                let position = match symbols.iter().position(|s| s.index == index) {
+                    Some(position) => position,
+                    None => {
+                        function = None;
+                        continue;
+                    }
+                };
+
+                let file = symbols[..position].iter().rev()
+                    .find(|s| matches!(s.storage_class, StorageClass::Filename))
+                    .and_then(|s| s.aux.iter().find_map(|aux| match aux {
+                        Symbol::Auxiliary { x_fname: Some(name), .. } => Some(name.clone()),
+                        _ => None,
+                    }));
+
+                // The function's opening line is in the aux entry of
+                // the ".bf" symbol that follows it.
+                let first = symbols[position + 1..].iter()
+                    .find(|s| matches!(s.storage_class, StorageClass::BeginEndFunc) && s.name == ".bf")
+                    .and_then(|s| s.aux.iter().find_map(|aux| match aux {
+                        Symbol::Auxiliary { x_lnno, .. } => Some(u32::from(*x_lnno)),
+                        _ => None,
+                    }))
+                    .unwrap_or(1);
+
+                lines.push(SourceLine {
+                    addr: symbols[position].value,
+                    file: file.clone(),
+                    line: first,
+                });
+
+                function = Some((file, first));
+            } else if let Some((file, first)) = &function {
+                lines.push(SourceLine {
+                    addr: entry.addr,
+                    file: file.clone(),
+                    line: first + u32::from(entry.lnno) - 1,
+                });
+            }
+        }
+
+        lines
+    }
+
+    ///
+    /// Locate the named function, returning the number of the section
+    /// containing it and the start and end offsets of its code within
+    /// that section's data.
+    ///
+    /// The function's size comes from its auxiliary entry's x_fsize if
+    /// the symbol is typed as a function and has one; other symbols use
+    /// those bytes for something else. Otherwise the function runs up to
+    /// the next function symbol in the same section, or to the end of
+    /// the section.
+    ///
+    pub fn function_extent(&self, name: &str) -> Option<(usize, usize, usize)> {
+        let symbols = self.resolved_symbols();
+
+        let sym = symbols.iter().find(|s| s.name == name && s.section > 0)?;
+        let sec_num = sym.section as usize - 1;
+        let section = self.sections.get(sec_num)?;
+        let header = &section.header;
+
+        if !section.address_range().contains(&sym.value) {
+            return None;
+        }
+
+        let start = sym.value - header.vaddr;
+
+        let end = match sym.function_size() {
+            Some(size) => start.saturating_add(size),
+            None => symbols.iter()
+                .filter(|s| s.section == sym.section && (s.stype >> 4) & 3 == DT_FCN)
+                .map(|s| s.value)
+                .filter(|&value| value > sym.value)
+                .min()
+                .map(|value| value - header.vaddr)
+                .unwrap_or(header.size),
+        };
+
+        Some((sec_num, start as usize, end.min(header.size) as usize))
+    }
+
+    ///
+    /// Find every pair of sections whose data overlaps in the file, or
+    /// whose allocated address ranges overlap. Either usually means the
+    /// file is corrupt.
+    ///
+    pub fn overlapping_sections(&self) -> Vec<SectionOverlap> {
+        fn overlaps<T: PartialOrd>(a: &Range<T>, b: &Range<T>) -> bool {
+            a.start < a.end && b.start < b.end && a.start < b.end && b.start < a.end
+        }
+
+        let mut overlaps_found = vec!();
+
+        for (first, a) in self.sections.iter().enumerate() {
+            for (second, b) in self.sections.iter().enumerate().skip(first + 1) {
+                if overlaps(&a.file_range(), &b.file_range()) {
+                    overlaps_found.push(SectionOverlap { first, second, kind: OverlapKind::FileOffset });
+                }
+
+                if a.is_allocated() && b.is_allocated() &&
+                    overlaps(&a.address_range(), &b.address_range()) {
+                    overlaps_found.push(SectionOverlap { first, second, kind: OverlapKind::Address });
+                }
+            }
+        }
+
+        overlaps_found
+    }
+
+    ///
+    /// The text sections, with their section numbers. A file may have
+    /// none, for example if it holds only data or no sections at all.
+    ///
+    pub fn code_sections(&self) -> impl Iterator<Item = (usize, &Section<'a>)> {
+        self.sections.iter().enumerate()
+            .filter(|(_, s)| s.header.section_flags().contains(SectionFlags::STYP_TEXT))
+    }
+
+    ///
+    /// Find the number of the section whose virtual address range
+    /// contains `addr`.
+    ///
+    pub fn section_containing(&self, addr: u32) -> Option<usize> {
+        self.sections.iter().position(|s| s.address_range().contains(&addr))
+    }
+
+    ///
+    /// Translate a virtual address to the number of the section holding
+    /// it and its offset in the file. Addresses in sections with no
+    /// data in the file, such as .bss, have no file offset.
+    ///
+    pub fn vaddr_to_offset(&self, vaddr: u32) -> Option<(usize, usize)> {
+        self.sections.iter().enumerate()
+            .filter(|(_, s)| s.is_allocated() && s.header.scnptr != 0)
+            .find(|(_, s)| s.address_range().contains(&vaddr))
+            .and_then(|(i, s)| {
+                // Widen before adding, so that an offset near 4 GiB
+                // can't wrap on a 32-bit host.
+                let offset = u64::from(s.header.scnptr) + u64::from(vaddr - s.header.vaddr);
+                usize::try_from(offset).ok().map(|offset| (i, offset))
+            })
+    }
+
+    ///
+    /// Read a NUL-terminated string of printable ASCII (or tab,
+    /// newline or carriage return) at a virtual address, stopping
+    /// after `max_len` characters. Returns the string and whether it
+    /// was cut short, or `None` if the address isn't in a section's
+    /// data or doesn't hold at least two such characters.
+    ///
+    pub fn string_at(&self, vaddr: u32, max_len: usize) -> Option<(String, bool)> {
+        let section = self.sections.iter()
+            .filter(|s| s.is_allocated() && !s.data.is_empty())
+            .find(|s| s.address_range().contains(&vaddr))?;
+        let bytes = section.data.get((vaddr - section.header.vaddr) as usize..)?;
+
+        let text_len = bytes.iter()
+            .take_while(|&&b| (0x20..0x7f).contains(&b) || b == b'\t' || b == b'\n' || b == b'\r')
+            .count();
+
+        // Anything else before the NUL means this isn't text.
+        if text_len < 2 || !matches!(bytes.get(text_len), Some(0) | None) {
+            return None;
+        }
+
+        let shown = text_len.min(max_len);
+        let text = str::from_utf8(&bytes[..shown]).ok()?;
+
+        Some((text.to_owned(), shown < text_len))
+    }
+
+    ///
+    /// Translate a file offset to the number of the section holding it
+    /// and its virtual address. Offsets outside every section's raw
+    /// data, such as those in the headers, have no address.
+    ///
+    pub fn offset_to_vaddr(&self, offset: usize) -> Option<(usize, u32)> {
+        let offset = offset as u64;
+
+        self.sections.iter().enumerate()
+            .filter(|(_, s)| s.is_allocated())
+            .find(|(_, s)| s.file_range().contains(&offset))
+            .map(|(i, s)| (i, s.header.vaddr.wrapping_add((offset - u64::from(s.header.scnptr)) as u32)))
+    }
+
+    pub fn section_data(&self, sec_num: usize) -> Option<&[u8]> {
         if let Some(section) = &self.sections.get(sec_num) {
             return Some(&section.data);
         }
 
         None
     }
+
+    ///
+    /// The data of a section, by number. A section with no data in the
+    /// file, such as .bss, gives an empty slice; only a section number
+    /// past the end of the section table is an error.
+    ///
+    pub fn try_section_data(&self, sec_num: usize) -> Result<&[u8], OffsetError> {
+        match self.sections.get(sec_num) {
+            Some(section) => Ok(&section.data),
+            None => Err(OffsetError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small object with text, data, relocations, line numbers,
+    /// symbols and a string table.
+    static HELLO: &[u8] = include_bytes!("../tests/golden/hello.o");
+
+    /// A file header with the WE32000 magic number.
+    fn file_header(section_count: u16, symbol_table_offset: u32, symbol_count: u32) -> Vec<u8> {
+        let mut buf = vec![0x01, 0x70];
+        buf.extend_from_slice(&section_count.to_be_bytes());
+        buf.extend_from_slice(&0u32.to_be_bytes());
+        buf.extend_from_slice(&symbol_table_offset.to_be_bytes());
+        buf.extend_from_slice(&symbol_count.to_be_bytes());
+        buf.extend_from_slice(&[0, 0, 0, 0]);
+        buf
+    }
+
+    /// A ".text" section header.
+    fn section_header(size: u32, scnptr: u32, relptr: u32, nreloc: u16) -> Vec<u8> {
+        let mut buf = b".text\0\0\0".to_vec();
+        buf.extend_from_slice(&[0; 8]);
+        buf.extend_from_slice(&size.to_be_bytes());
+        buf.extend_from_slice(&scnptr.to_be_bytes());
+        buf.extend_from_slice(&relptr.to_be_bytes());
+        buf.extend_from_slice(&[0; 4]);
+        buf.extend_from_slice(&nreloc.to_be_bytes());
+        buf.extend_from_slice(&[0; 2]);
+        buf.extend_from_slice(&0x20u32.to_be_bytes());
+        buf
+    }
+
+    #[test]
+    fn header_only_file_is_empty() {
+        // No sections, no optional header and no symbols, with nothing
+        // after the file header.
+        let buf = file_header(0, 0, 0);
+        let container = FileContainer::read(&buf).unwrap();
+
+        assert!(container.sections.is_empty());
+        assert!(container.code_sections().next().is_none());
+        assert!(container.symbols.is_empty());
+    }
+
+    #[test]
+    fn dumps_of_empty_tables() {
+        let buf = file_header(0, 0, 0);
+        let empty = FileContainer::read(&buf).unwrap();
+
+        empty.dump_symbol_table();
+        assert!(empty.dump_relocation_table(0).is_err());
+        assert!(empty.dump_section_data(0).is_err());
+
+        // A section with no data and no relocations.
+        let mut buf = file_header(1, 0, 0);
+        buf.extend_from_slice(&section_header(0, 0, 0, 0));
+        let container = FileContainer::read(&buf).unwrap();
+
+        container.dump_symbol_table();
+        assert!(container.dump_relocation_table(0).is_ok());
+        assert!(container.dump_section_data(0).is_ok());
+        assert!(container.dump_section_data(1).is_err());
+    }
+
+    #[test]
+    fn short_final_data_rows() {
+        // Printable bytes, so that anything left over from an earlier
+        // row would show in the summary.
+        let data: Vec<u8> = (b'A'..=b'Z').chain(b'a'..=b'z').collect();
+        let full = data_row(0, &data[..16]);
+
+        for &len in &[1, 7, 8, 9, 15, 17, 31] {
+            let rows: Vec<String> = data[..len].chunks(16)
+                .enumerate()
+                .map(|(i, bytes)| data_row(i as u32 * 16, bytes))
+                .collect();
+            let last = rows.last().unwrap();
+            let tail = &data[(rows.len() - 1) * 16..len];
+
+            assert_eq!(last.len(), full.len(), "{} bytes: {}", len, last);
+
+            let (hex, ascii) = last.split_at(last.find("  | ").unwrap());
+            let shown: Vec<&str> = hex.split_whitespace().skip(1).collect();
+            let expected: Vec<String> = tail.iter().map(|b| format!("{:02x}", b)).collect();
+            assert_eq!(shown, expected, "{} bytes: {}", len, last);
+
+            let expected = format!("  | {:<16} |", String::from_utf8_lossy(tail));
+            assert_eq!(ascii, expected, "{} bytes: {}", len, last);
+        }
+    }
+
+    /// A file with one symbol, "main", and a string table holding
+    /// "a_long_symbol_name" at offset 4.
+    fn file_with_strings() -> Vec<u8> {
+        let mut buf = file_header(0, 20, 1);
+        buf.extend_from_slice(b"main\0\0\0\0");
+        buf.extend_from_slice(&[0; 10]);
+
+        let name = b"a_long_symbol_name\0";
+        buf.extend_from_slice(&(4 + name.len() as u32).to_be_bytes());
+        buf.extend_from_slice(name);
+        buf
+    }
+
+    /// Resolve a raw eight-byte name field as the symbol table would.
+    fn name_of(container: &FileContainer, n_name: [u8; 8]) -> String {
+        let n_zeroes = u32::from_be_bytes([n_name[0], n_name[1], n_name[2], n_name[3]]);
+        let n_offset = u32::from_be_bytes([n_name[4], n_name[5], n_name[6], n_name[7]]);
+        container.symbol_name(&n_name, n_zeroes, n_offset).into_owned()
+    }
+
+    #[test]
+    fn inline_name_starting_with_nul() {
+        let buf = file_with_strings();
+        let container = FileContainer::read(&buf).unwrap();
+
+        // The first four bytes aren't all zero, so this is an inline
+        // name, and a leading NUL makes it empty.
+        assert_eq!(name_of(&container, [0, b'a', b'b', b'c', 0, 0, 0, 4]), "");
+    }
+
+    #[test]
+    fn offset_into_string_table_size_is_empty() {
+        let buf = file_with_strings();
+        let container = FileContainer::read(&buf).unwrap();
+
+        for offset in 0..4 {
+            assert_eq!(name_of(&container, [0, 0, 0, 0, 0, 0, 0, offset]), "");
+        }
+    }
+
+    #[test]
+    fn long_name_from_string_table() {
+        let buf = file_with_strings();
+        let container = FileContainer::read(&buf).unwrap();
+
+        assert_eq!(name_of(&container, [0, 0, 0, 0, 0, 0, 0, 4]), "a_long_symbol_name");
+        assert_eq!(name_of(&container, *b"main\0\0\0\0"), "main");
+        assert_eq!(container.symbol_name_at(0).unwrap(), "main");
+    }
+
+    /// An auxiliary entry whose every field has a distinct value:
+    /// tagndx 7, lnno 0x10 and size 0x28 (or fsize 0x100028), lnnoptr
+    /// 0xa0004 and endndx 0x20001 (or dimensions 10, 4, 2 and 1), and
+    /// tvndx 3.
+    static AUX: [u8; 18] = [
+        0x00, 0x00, 0x00, 0x07,
+        0x00, 0x10, 0x00, 0x28,
+        0x00, 0x0a, 0x00, 0x04,
+        0x00, 0x02, 0x00, 0x01,
+        0x00, 0x03,
+    ];
+
+    fn read_aux() -> Symbol {
+        SymbolTableEntry::read_symbol(&mut Cursor::new(&AUX[..]), true, &StorageClass::ExternalDef,
+                                      NameEncoding::Utf8).unwrap()
+    }
+
+    #[test]
+    fn function_aux_entry() {
+        // A function returning int.
+        let aux = read_aux();
+
+        assert_eq!(AuxEntry::new(&aux, (DT_FCN << 4) | 4), Some(AuxEntry::Function {
+            tagndx: 7,
+            fsize: 0x100028,
+            lnnoptr: 0xa0004,
+            endndx: 0x20001,
+            tvndx: 3,
+        }));
+    }
+
+    #[test]
+    fn array_aux_entry() {
+        // An array of int.
+        let aux = read_aux();
+
+        assert_eq!(AuxEntry::new(&aux, (DT_ARY << 4) | 4), Some(AuxEntry::Array {
+            tagndx: 7,
+            lnno: 0x10,
+            size: 0x28,
+            dimen: [10, 4, 2, 1],
+            tvndx: 3,
+        }));
+    }
+
+    /// A primary symbol table entry with an inline name.
+    fn symbol(name: &[u8], value: u32, scnum: i16, n_type: u16, sclass: u8, numaux: u8) -> Vec<u8> {
+        let mut buf = name.to_vec();
+        buf.resize(8, 0);
+        buf.extend_from_slice(&value.to_be_bytes());
+        buf.extend_from_slice(&scnum.to_be_bytes());
+        buf.extend_from_slice(&n_type.to_be_bytes());
+        buf.extend_from_slice(&[sclass, numaux]);
+        buf
+    }
+
+    /// A file with a 0x40 byte .text section, its section symbol, whose
+    /// auxiliary entry gives its length, no relocations and 5 line
+    /// numbers, and a function, main, of 0x20 bytes at offset 0x10.
+    fn file_with_section_symbol() -> Vec<u8> {
+        let mut buf = file_header(1, 124, 4);
+        buf.extend_from_slice(&section_header(0x40, 60, 0, 0));
+        buf.extend_from_slice(&[0; 0x40]);
+
+        buf.extend_from_slice(&symbol(b".text", 0, 1, 0, 3, 1));
+        buf.extend_from_slice(&[0, 0, 0, 0x40, 0, 0, 0, 5]);
+        buf.extend_from_slice(&[0; 10]);
+
+        buf.extend_from_slice(&symbol(b"main", 0x10, 1, (DT_FCN << 4) | 4, 2, 1));
+        buf.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0x20]);
+        buf.extend_from_slice(&[0; 10]);
+
+        buf.extend_from_slice(&4u32.to_be_bytes());
+        buf
+    }
+
+    #[test]
+    fn only_functions_have_a_size() {
+        let buf = file_with_section_symbol();
+        let container = FileContainer::read(&buf).unwrap();
+        let symbols = container.resolved_symbols();
+
+        let text = symbols.iter().find(|s| s.name == ".text").unwrap();
+        assert_eq!(text.aux.len(), 1);
+        assert_eq!(text.function_size(), None);
+
+        let main = symbols.iter().find(|s| s.name == "main").unwrap();
+        assert_eq!(main.function_size(), Some(0x20));
+    }
+
+    #[test]
+    fn function_extent_ignores_non_function_aux() {
+        let buf = file_with_section_symbol();
+        let container = FileContainer::read(&buf).unwrap();
+
+        // The section symbol's line number count is not a size, so it
+        // runs up to the next function.
+        assert_eq!(container.function_extent(".text"), Some((0, 0, 0x10)));
+        assert_eq!(container.function_extent("main"), Some((0, 0x10, 0x30)));
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        assert!(FileContainer::read(HELLO).is_ok());
+
+        for len in 0..HELLO.len() {
+            assert!(FileContainer::read(&HELLO[..len]).is_err(), "read {} of {} bytes", len, HELLO.len());
+            // Lenient reading may succeed, but must not panic either.
+            let _ = FileContainer::read_lenient(&HELLO[..len]);
+        }
+    }
+
+    #[test]
+    fn huge_symbol_count_is_an_error() {
+        let buf = file_header(0, 20, u32::MAX);
+        assert!(matches!(FileContainer::read(&buf), Err(CoffError::BadSymbols)));
+    }
+
+    #[test]
+    fn huge_section_counts_are_errors() {
+        let sections = [
+            section_header(u32::MAX, 60, 0, 0),
+            section_header(16, u32::MAX, 0, 0),
+            section_header(0, 0, 60, u16::MAX),
+            section_header(0, 0, u32::MAX, 1),
+        ];
+
+        for section in &sections {
+            let mut buf = file_header(1, 0, 0);
+            buf.extend_from_slice(section);
+            buf.extend_from_slice(&[0x70; 16]);
+
+            assert!(matches!(FileContainer::read(&buf), Err(CoffError::SectionOutOfBounds { .. })));
+            assert!(FileContainer::read_lenient(&buf).is_ok());
+        }
+
+        // More section headers than the file has room for.
+        assert!(FileContainer::read(&file_header(u16::MAX, 0, 0)).is_err());
+    }
+
+    #[test]
+    fn random_bytes_do_not_panic() {
+        // A fixed xorshift sequence, so any failure can be reproduced.
+        let mut state: u32 = 0x3b2;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+
+        for _ in 0..2000 {
+            let len = (next() % 512) as usize;
+            let mut buf: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+
+            // Give most buffers a valid magic number and a plausible
+            // section count, so that they get past the file header.
+            // Without one, the file is always rejected.
+            if len >= 4 && next() % 4 != 0 {
+                buf[..4].copy_from_slice(&[0x01, 0x70, 0x00, (next() % 4) as u8]);
+                let _ = FileContainer::read(&buf);
+            } else {
+                assert!(FileContainer::read(&buf).is_err());
+            }
+
+            let _ = FileContainer::read_lenient(&buf);
+
+            // Corrupt one byte of a real file.
+            let mut hello = HELLO.to_vec();
+            let at = next() as usize % hello.len();
+            hello[at] = next() as u8;
+
+            let _ = FileContainer::read(&hello);
+            let _ = FileContainer::read_lenient(&hello);
+        }
+    }
 }
@@ -1,11 +1,10 @@
 #![allow(clippy::unreadable_literal)]
 
+#[cfg(feature = "std")]
 use std::io::Cursor;
 
-use byteorder::{LittleEndian, ReadBytesExt};
-
 use crate::errors::DecodeError;
-use std::fmt;
+use core::fmt;
 
 const R_FP: usize = 9;
 const R_AP: usize = 10;
@@ -53,6 +52,85 @@ pub enum Data {
     UWord,
 }
 
+/// How an instruction affects the flow of control.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Flow {
+    // Execution continues with the next instruction
+    Normal,
+    // Unconditional branch or jump; never falls through
+    Branch,
+    // Conditional branch; may fall through
+    ConditionalBranch,
+    // Subroutine or procedure call; returns to the next instruction
+    Call,
+    // Return; never falls through
+    Return,
+    // Conditional return; may fall through
+    ConditionalReturn,
+}
+
+/// The flag test of a conditional branch or return.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Condition {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    GreaterUnsigned,
+    GreaterOrEqualUnsigned,
+    LessUnsigned,
+    LessOrEqualUnsigned,
+    OverflowClear,
+    OverflowSet,
+}
+
+impl Condition {
+    /// The condition in words, e.g. "greater or equal, signed".
+    pub fn description(self) -> &'static str {
+        match self {
+            Condition::Equal => "equal",
+            Condition::NotEqual => "not equal",
+            Condition::Greater => "greater, signed",
+            Condition::GreaterOrEqual => "greater or equal, signed",
+            Condition::Less => "less, signed",
+            Condition::LessOrEqual => "less or equal, signed",
+            Condition::GreaterUnsigned => "greater, unsigned",
+            Condition::GreaterOrEqualUnsigned => "greater or equal, unsigned",
+            Condition::LessUnsigned => "less, unsigned",
+            Condition::LessOrEqualUnsigned => "less or equal, unsigned",
+            Condition::OverflowClear => "overflow clear",
+            Condition::OverflowSet => "overflow set",
+        }
+    }
+
+    /// The test of the PSW flags, as given in the WE32100 manual.
+    pub fn flag_test(self) -> &'static str {
+        match self {
+            Condition::Equal => "Z == 1",
+            Condition::NotEqual => "Z == 0",
+            Condition::Greater => "N == 0 and Z == 0",
+            Condition::GreaterOrEqual => "N == 0 or Z == 1",
+            Condition::Less => "N == 1 and Z == 0",
+            Condition::LessOrEqual => "N == 1 or Z == 1",
+            Condition::GreaterUnsigned => "C == 0 and Z == 0",
+            Condition::GreaterOrEqualUnsigned => "C == 0 or Z == 1",
+            Condition::LessUnsigned => "C == 1 and Z == 0",
+            Condition::LessOrEqualUnsigned => "C == 1 or Z == 1",
+            Condition::OverflowClear => "V == 0",
+            Condition::OverflowSet => "V == 1",
+        }
+    }
+}
+
+/// Known register contents, used to resolve register-relative operands.
+/// A register whose value is not known is `None`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RegisterFile {
+    pub regs: [Option<u32>; 16],
+}
+
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub struct Operand {
     size: u8,
@@ -61,6 +139,10 @@ pub struct Operand {
     expanded_type: Option<Data>,
     register: Option<usize>,
     embedded: u32,
+    // True if this operand's own descriptor carried the expanded type,
+    // rather than inheriting it from an earlier operand.
+    typed: bool,
+    offset: usize,
     cursor: usize,
     bytes: [u8; 32],
 }
@@ -81,19 +163,76 @@ impl Operand {
             expanded_type,
             register,
             embedded,
+            typed: false,
+            offset: 0,
             cursor: 0,
             bytes: [0; 32],
         }
     }
 
+    /// Clear every field, so that nothing from a previously decoded
+    /// instruction survives.
     fn reset(&mut self) {
-        self.cursor = 0;
+        *self = Operand::new(0, AddrMode::None, Data::None, None, None, 0);
     }
 
-    fn byte_size(&self) -> u8 {
+    pub fn byte_size(&self) -> u8 {
         self.cursor as u8
     }
 
+    /// The raw bytes of the operand, including its descriptor.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.cursor]
+    }
+
+    /// Where the operand's first byte is in the buffer it was decoded
+    /// from. For a patching tool, adding the file offset of that buffer
+    /// gives the operand's place in the file.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The addressing mode. Literal operands of branches and calls,
+    /// which have no descriptor byte, are `AddrMode::None`.
+    pub fn mode(&self) -> AddrMode {
+        self.mode
+    }
+
+    /// The register the operand uses, if any. %fp and %ap are implied
+    /// by the short offset modes.
+    pub fn register(&self) -> Option<usize> {
+        self.register
+    }
+
+    /// The value embedded in the instruction stream: the immediate,
+    /// literal, displacement, or absolute address, depending on the
+    /// mode. Byte and halfword values are zero-extended.
+    pub fn embedded(&self) -> u32 {
+        self.embedded
+    }
+
+    /// The operand's data type, as given by the instruction. An
+    /// immediate has the type of its own size instead, so a byte
+    /// immediate in a word instruction is `Data::Byte`.
+    pub fn data_type(&self) -> Data {
+        self.data_type
+    }
+
+    /// The expanded type in effect for this operand, if any.
+    pub fn expanded_type(&self) -> Option<Data> {
+        self.expanded_type
+    }
+
+    /// The operand's descriptor byte, after any expanded-type byte.
+    /// Literal operands have none.
+    pub fn descriptor(&self) -> Option<u8> {
+        match self.mode {
+            AddrMode::None => None,
+            _ if self.typed => Some(self.bytes[1]),
+            _ => Some(self.bytes[0]),
+        }
+    }
+
     fn append_u8(&mut self, b: u8) {
         if self.cursor < 31 {
             self.bytes[self.cursor] = b;
@@ -118,30 +257,165 @@ impl Operand {
             self.cursor += 4;
         }
     }
+
+    /// Compute the memory address this operand refers to.
+    ///
+    /// Absolute modes always resolve. Register-relative modes resolve
+    /// only if `regs` supplies a value for the base register. For the
+    /// deferred modes, the result is the address of the pointer, not
+    /// the address it points to. Register, literal, and immediate
+    /// operands don't refer to memory and return `None`.
+    pub fn effective_address(&self, regs: Option<&RegisterFile>) -> Option<u32> {
+        let base = || self.register
+            .and_then(|r| regs.and_then(|regs| regs.regs.get(r).cloned().flatten()));
+
+        match self.mode {
+            AddrMode::Absolute | AddrMode::AbsoluteDeferred => Some(self.embedded),
+            AddrMode::ByteDisplacement | AddrMode::ByteDisplacementDeferred => {
+                base().map(|b| b.wrapping_add(i32::from(self.embedded as u8 as i8) as u32))
+            }
+            AddrMode::HalfwordDisplacement | AddrMode::HalfwordDisplacementDeferred => {
+                base().map(|b| b.wrapping_add(i32::from(self.embedded as u16 as i16) as u32))
+            }
+            AddrMode::WordDisplacement | AddrMode::WordDisplacementDeferred |
+            AddrMode::APShortOffset | AddrMode::FPShortOffset => {
+                base().map(|b| b.wrapping_add(self.embedded))
+            }
+            AddrMode::RegisterDeferred => base(),
+            _ => None,
+        }
+    }
+}
+
+/// The assembler's name for a register number, e.g. 9 => "%fp".
+pub fn register_name(r: usize) -> &'static str {
+    match r {
+        0 => "%r0",
+        1 => "%r1",
+        2 => "%r2",
+        3 => "%r3",
+        4 => "%r4",
+        5 => "%r5",
+        6 => "%r6",
+        7 => "%r7",
+        8 => "%r8",
+        9 => "%fp",
+        10 => "%ap",
+        11 => "%psw",
+        12 => "%sp",
+        13 => "%pcbp",
+        14 => "%isp",
+        15 => "%pc",
+        _ => "%??",
+    }
+}
+
+/// How register names are written.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RegStyle {
+    /// The assembler's names: %r0 to %r8, then %fp, %ap, %psw, %sp,
+    /// %pcbp, %isp and %pc.
+    Special,
+    /// Every register by number, %r0 to %r15.
+    Numeric,
+    /// The assembler's names without the "%", e.g. r0 and fp.
+    Bare,
+}
+
+/// A register number that displays as its name in the given style.
+struct RegisterName(Option<usize>, RegStyle);
+
+impl fmt::Display for RegisterName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.0 {
+            Some(r) => register_name(r),
+            None => "%??",
+        };
+
+        match (self.1, self.0) {
+            (RegStyle::Numeric, Some(r)) if r < 16 => write!(f, "%r{}", r),
+            (RegStyle::Bare, _) => write!(f, "{}", &name[1..]),
+            _ => write!(f, "{}", name),
+        }
+    }
+}
+
+/// An Operand formatted with a particular register naming style, as
+/// returned by `Operand::display`.
+pub struct OperandDisplay<'a> {
+    op: &'a Operand,
+    style: RegStyle,
+}
+
+impl<'a> fmt::Display for OperandDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.op.write_with(f, self.style)
+    }
 }
 
 impl fmt::Display for Operand {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_with(f, RegStyle::Special)
+    }
+}
 
-        let reg_string = match self.register {
-            Some(0) => "%r0",
-            Some(1) => "%r1",
-            Some(2) => "%r2",
-            Some(3) => "%r3",
-            Some(4) => "%r4",
-            Some(5) => "%r5",
-            Some(6) => "%r6",
-            Some(7) => "%r7",
-            Some(8) => "%r8",
-            Some(9) => "%fp",
-            Some(10) => "%ap",
-            Some(11) => "%psw",
-            Some(12) => "%sp",
-            Some(13) => "%pcbp",
-            Some(14) => "%isp",
-            Some(15) => "%pc",
-            _ => "%??",
-        };
+impl Operand {
+    /// Format the operand with registers named in the given style.
+    /// Plain `Display` uses `RegStyle::Special`.
+    pub fn display(&self, style: RegStyle) -> OperandDisplay<'_> {
+        OperandDisplay {
+            op: self,
+            style,
+        }
+    }
+
+    fn write_with(&self, f: &mut fmt::Formatter, style: RegStyle) -> fmt::Result {
+        let reg_string = RegisterName(self.register, style);
+
+        // An explicit expanded type is written as the assembler does,
+        // e.g. "{sbyte}%r0".
+        if self.typed {
+            match self.expanded_type {
+                Some(Data::Word) => write!(f, "{{word}}")?,
+                Some(Data::UWord) => write!(f, "{{uword}}")?,
+                Some(Data::Half) => write!(f, "{{half}}")?,
+                Some(Data::UHalf) => write!(f, "{{uhalf}}")?,
+                Some(Data::Byte) => write!(f, "{{byte}}")?,
+                Some(Data::SByte) => write!(f, "{{sbyte}}")?,
+                _ => {}
+            }
+        }
+
+        // Under an expanded type, immediates and literals are shown as
+        // the type interprets them: signed types in signed decimal, and
+        // unsigned types as an unsigned value of the type's width.
+        match (self.mode, self.expanded_type) {
+            (AddrMode::ByteImmediate, Some(Data::Word)) |
+            (AddrMode::ByteImmediate, Some(Data::Half)) |
+            (AddrMode::ByteImmediate, Some(Data::SByte)) => {
+                return write!(f, "&{}", (self.embedded as u8) as i8);
+            }
+            (AddrMode::HalfwordImmediate, Some(Data::Word)) |
+            (AddrMode::HalfwordImmediate, Some(Data::Half)) |
+            (AddrMode::HalfwordImmediate, Some(Data::SByte)) => {
+                return write!(f, "&{}", (self.embedded as u16) as i16);
+            }
+            (AddrMode::WordImmediate, Some(Data::Word)) |
+            (AddrMode::WordImmediate, Some(Data::Half)) |
+            (AddrMode::WordImmediate, Some(Data::SByte)) => {
+                return write!(f, "&{}", self.embedded as i32);
+            }
+            (AddrMode::NegativeLiteral, Some(Data::UWord)) => {
+                return write!(f, "&0x{:x}", i32::from((self.embedded as u8) as i8) as u32);
+            }
+            (AddrMode::NegativeLiteral, Some(Data::UHalf)) => {
+                return write!(f, "&0x{:x}", i16::from((self.embedded as u8) as i8) as u16);
+            }
+            (AddrMode::NegativeLiteral, Some(Data::Byte)) => {
+                return write!(f, "&0x{:x}", self.embedded as u8);
+            }
+            _ => {}
+        }
 
         match self.mode {
             AddrMode::Absolute => write!(f, "$0x{:x}", self.embedded)?,
@@ -152,8 +426,8 @@ impl fmt::Display for Operand {
             AddrMode::HalfwordDisplacementDeferred => write!(f, "*0x{:x}({})", self.embedded as u16, reg_string)?,
             AddrMode::WordDisplacement => write!(f, "0x{:x}({})", self.embedded, reg_string)?,
             AddrMode::WordDisplacementDeferred => write!(f, "*0x{:x}({})", self.embedded, reg_string)?,
-            AddrMode::APShortOffset => write!(f, "{}(%ap)", self.embedded)?,
-            AddrMode::FPShortOffset => write!(f, "{}(%fp)", self.embedded)?,
+            AddrMode::APShortOffset => write!(f, "{}({})", self.embedded, RegisterName(Some(R_AP), style))?,
+            AddrMode::FPShortOffset => write!(f, "{}({})", self.embedded, RegisterName(Some(R_FP), style))?,
             AddrMode::ByteImmediate => write!(f, "&{}", self.embedded)?,
             AddrMode::HalfwordImmediate => write!(f, "&0x{:x}", self.embedded)?,
             AddrMode::WordImmediate => write!(f, "&0x{:x}", self.embedded)?,
@@ -161,7 +435,12 @@ impl fmt::Display for Operand {
             AddrMode::NegativeLiteral => write!(f, "&{}", (self.embedded as u8) as i8)?,
             AddrMode::Register => write!(f, "{}", reg_string)?,
             AddrMode::RegisterDeferred => write!(f, "({})", reg_string)?,
-            AddrMode::None => write!(f, "{}", self.embedded)?,
+            // Byte and halfword literals are PC-relative displacements.
+            AddrMode::None => match self.data_type {
+                Data::Byte => write!(f, "{}", (self.embedded as u8) as i8)?,
+                Data::Half => write!(f, "{}", (self.embedded as u16) as i16)?,
+                _ => write!(f, "{}", self.embedded)?,
+            },
         }
 
         Ok(())
@@ -185,18 +464,285 @@ pub struct Instruction {
     pub operands: [Operand; 4],
 }
 
+/// Letter case used for mnemonics.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Case {
+    Upper,
+    Lower,
+}
+
+/// Options controlling how an Instruction is formatted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FormatOptions {
+    /// Column at which the mnemonic starts, after the byte dump.
+    pub pad_width: usize,
+    /// How much detail to add to each line. Each level adds to the
+    /// ones below it:
+    ///
+    ///   1. Instruction addresses and resolved branch targets (added by
+    ///      the caller, which knows the address and symbols)
+    ///   2. The condition flags each instruction sets, with notes on
+    ///      writes to %psw, three-operand forms that work in place, the
+    ///      conditions of conditional branches and returns, MOVTRW, the
+    ///      process-switching instructions, what RET, RSB and RETG
+    ///      unwind, and how pushes and pops move %sp; also the implicit
+    ///      %r0 of the SWAP*I instructions, what an address-of source
+    ///      really moves, and ROTW's rotate count
+    ///   3. The descriptor byte of each operand, split into its mode
+    ///      and register fields
+    pub verbosity: u8,
+    /// Letter case of the mnemonic.
+    pub case: Case,
+    /// How registers in operands are named.
+    pub reg_style: RegStyle,
+    /// Hex digits in the instruction addresses that callers print
+    /// ahead of each line. Zero means just enough for the highest
+    /// address in the listing.
+    pub address_width: usize,
+    /// Separate the opcode and each operand in the byte dump with a
+    /// bar, e.g. "84 | 40 | 41", to show the instruction's structure.
+    pub group_bytes: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            pad_width: 30,
+            verbosity: 0,
+            case: Case::Upper,
+            reg_style: RegStyle::Special,
+            address_width: 8,
+            group_bytes: false,
+        }
+    }
+}
+
+pub struct InstructionDisplay<'a> {
+    insn: &'a Instruction,
+    opts: &'a FormatOptions,
+}
+
+impl Instruction {
+    /// If this is a PC-relative branch (including the BSB subroutine
+    /// branches), return its target given the address of the instruction.
+    pub fn branch_target(&self, addr: u32) -> Option<u32> {
+        if self.operand_count == 0 || self.operands[0].mode != AddrMode::None {
+            return None;
+        }
+
+        let disp = match self.data_type {
+            Data::Byte => i32::from(self.operands[0].embedded as u8 as i8),
+            Data::Half => i32::from(self.operands[0].embedded as u16 as i16),
+            _ => return None,
+        };
+
+        Some(addr.wrapping_add(disp as u32))
+    }
+
+    /// Classify the instruction's effect on control flow.
+    pub fn flow(&self) -> Flow {
+        match self.opcode {
+            0x24 | 0x7A | 0x7B => Flow::Branch,
+            0x2C | 0x34 | 0x36 | 0x37 => Flow::Call,
+            0x08 | 0x78 | 0x3045 | 0x30c8 => Flow::Return,
+            0x40 | 0x44 | 0x48 | 0x4C | 0x50 | 0x54 | 0x58 | 0x5C |
+            0x60 | 0x64 | 0x68 | 0x6C | 0x74 | 0x7C => Flow::ConditionalReturn,
+            0x42..=0x7F if self.branch_target(0).is_some() => Flow::ConditionalBranch,
+            _ => Flow::Normal,
+        }
+    }
+
+    /// True if this instruction ends a basic block.
+    pub fn ends_block(&self) -> bool {
+        !matches!(self.flow(), Flow::Normal | Flow::Call)
+    }
+
+    /// True if execution can never continue with the next instruction.
+    pub fn is_terminator(&self) -> bool {
+        matches!(self.flow(), Flow::Branch | Flow::Return)
+    }
+
+    /// The condition a conditional branch or return tests. The
+    /// conditional opcodes come in groups of four: the return, an
+    /// unused opcode, then the halfword and byte branches. Equality has
+    /// two groups each, one meant for use after signed comparisons and
+    /// one after unsigned, but they test the same flags and the
+    /// assembler gives them the same names.
+    pub fn condition(&self) -> Option<Condition> {
+        match self.opcode {
+            0x40 | 0x42 | 0x43 => Some(Condition::GreaterOrEqual),
+            0x44 | 0x46 | 0x47 => Some(Condition::Greater),
+            0x48 | 0x4A | 0x4B => Some(Condition::Less),
+            0x4C | 0x4E | 0x4F => Some(Condition::LessOrEqual),
+            0x50 | 0x52 | 0x53 => Some(Condition::GreaterOrEqualUnsigned),
+            0x54 | 0x56 | 0x57 => Some(Condition::GreaterUnsigned),
+            0x58 | 0x5A | 0x5B => Some(Condition::LessUnsigned),
+            0x5C | 0x5E | 0x5F => Some(Condition::LessOrEqualUnsigned),
+            0x60 | 0x62 | 0x63 => Some(Condition::OverflowClear),
+            0x64 | 0x66 | 0x67 => Some(Condition::NotEqual),
+            0x68 | 0x6A | 0x6B => Some(Condition::OverflowSet),
+            0x6C | 0x6E | 0x6F => Some(Condition::Equal),
+            0x74 | 0x76 | 0x77 => Some(Condition::NotEqual),
+            0x7C | 0x7E | 0x7F => Some(Condition::Equal),
+            _ => None,
+        }
+    }
+
+    /// Describe any operands the instruction uses that are not encoded
+    /// in the instruction stream.
+    pub fn implicit_operands(&self) -> Option<&'static str> {
+        match self.opcode {
+            // The interlocked swaps exchange the destination with %r0.
+            0x1C | 0x1E | 0x1F => Some("exchanges with %r0"),
+            _ => None,
+        }
+    }
+
+    /// For instructions that do more than their operands suggest, a
+    /// short description following the WE32100 manual: MOVTRW, which
+    /// moves an address through the MMU's translation rather than the
+    /// address itself, the process-switching instructions, which
+    /// change the execution level and process context, and the
+    /// returns, which each unwind a different kind of call.
+    pub fn semantics_note(&self) -> Option<&'static str> {
+        match self.opcode {
+            0x08 => Some("returns from a CALL: pops the return address and %ap, and resets %sp to the old %ap"),
+            0x78 => Some("returns from a BSB or JSB: pops the return address"),
+            0x3045 => Some("returns from a GATE: pops the PC and PSW, to a level no more privileged"),
+            0x0c => Some("stores the physical address the MMU translates the source's address to"),
+            0x3061 => Some("enters a more privileged level through the gate tables indexed by %r1 and %r0"),
+            0x30ac => Some("privileged: saves the process in its PCB and switches to the PCB %r0 points to"),
+            0x30c8 => Some("privileged: pops a PCB pointer off the interrupt stack and resumes that process"),
+            _ => None,
+        }
+    }
+
+    /// How many bytes a push or pop moves the stack pointer. The
+    /// WE32100 stack grows upward, so a push adds to %sp and a pop
+    /// subtracts from it.
+    pub fn stack_effect(&self) -> Option<i32> {
+        match self.name {
+            "PUSHW" | "PUSHAW" => Some(4),
+            "POPW" => Some(-4),
+            _ => None,
+        }
+    }
+
+    /// The condition flags (N, Z, V, C) this instruction modifies,
+    /// following the per-instruction condition code descriptions in the
+    /// WE32100 manual. Instructions that reload the whole PSW report
+    /// "PSW". Returns `None` if the flags are left unchanged.
+    pub fn flag_effects(&self) -> Option<&'static str> {
+        match self.name {
+            "MOVB" | "MOVH" | "MOVW" | "MOVAW" | "MOVTRW" |
+            "MCOMB" | "MCOMH" | "MCOMW" | "MNEGB" | "MNEGH" | "MNEGW" |
+            "CLRB" | "CLRH" | "CLRW" | "TSTB" | "TSTH" | "TSTW" |
+            "CMPB" | "CMPH" | "CMPW" | "BITB" | "BITH" | "BITW" |
+            "INCB" | "INCH" | "INCW" | "DECB" | "DECH" | "DECW" |
+            "ADDB2" | "ADDH2" | "ADDW2" | "ADDB3" | "ADDH3" | "ADDW3" |
+            "SUBB2" | "SUBH2" | "SUBW2" | "SUBB3" | "SUBH3" | "SUBW3" |
+            "MULB2" | "MULH2" | "MULW2" | "MULB3" | "MULH3" | "MULW3" |
+            "DIVB2" | "DIVH2" | "DIVW2" | "DIVB3" | "DIVH3" | "DIVW3" |
+            "MODB2" | "MODH2" | "MODW2" | "MODB3" | "MODH3" | "MODW3" |
+            "ANDB2" | "ANDH2" | "ANDW2" | "ANDB3" | "ANDH3" | "ANDW3" |
+            "ORB2" | "ORH2" | "ORW2" | "ORB3" | "ORH3" | "ORW3" |
+            "XORB2" | "XORH2" | "XORW2" | "XORB3" | "XORH3" | "XORW3" |
+            "ALSW3" | "ARSB3" | "ARSH3" | "ARSW3" |
+            "LLSB3" | "LLSH3" | "LLSW3" | "LRSW3" | "ROTW" |
+            "EXTFB" | "EXTFH" | "EXTFW" | "INSFB" | "INSFH" | "INSFW" |
+            "PUSHW" | "PUSHAW" | "POPW" |
+            "SWAPBI" | "SWAPHI" | "SWAPWI" => Some("NZVC"),
+            "RETG" | "RETPS" | "GATE" | "CALLPS" => Some("PSW"),
+            _ => None,
+        }
+    }
+
+    /// The number of opcode bytes: 2 for the halfword opcodes that
+    /// start with 0x30 (e.g. RETG, 0x3045), and 1 for the rest.
+    pub fn opcode_len(&self) -> usize {
+        if self.opcode > 0xff { 2 } else { 1 }
+    }
+
+    /// Reconstruct the bytes the instruction was decoded from: the
+    /// opcode followed by each operand's raw bytes.
+    #[cfg(feature = "std")]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = if self.opcode_len() == 2 {
+            vec![(self.opcode >> 8) as u8, self.opcode as u8]
+        } else {
+            vec![self.opcode as u8]
+        };
+
+        for op in &self.operands[..self.operand_count as usize] {
+            bytes.extend_from_slice(op.bytes());
+        }
+
+        bytes
+    }
+
+    /// True for the NOP family, which compilers use as padding.
+    pub fn is_nop(&self) -> bool {
+        matches!(self.opcode, 0x70 | 0x72 | 0x73)
+    }
+
+    /// True if the instruction uses the address of its first operand
+    /// rather than the value stored there, as MOVAW and PUSHAW do.
+    pub fn takes_address(&self) -> bool {
+        matches!(self.opcode, 0x04 | 0xE0)
+    }
+
+    /// True for a three-operand form such as ADDW3 whose destination
+    /// is the same as one of its sources, making it equivalent to the
+    /// two-operand form.
+    pub fn is_in_place(&self) -> bool {
+        if self.operand_count != 3 {
+            return false;
+        }
+
+        match operands_for_mnemonic(self.name) {
+            Some([OpType::Src, OpType::Src, OpType::Dest, OpType::None]) => {}
+            _ => return false,
+        }
+
+        let dest = &self.operands[2];
+
+        self.operands[..2].iter().any(|src| {
+            src.mode == dest.mode && src.register == dest.register && src.embedded == dest.embedded
+        })
+    }
+
+    /// Return a value that formats the instruction with the given options.
+    pub fn display<'a>(&'a self, opts: &'a FormatOptions) -> InstructionDisplay<'a> {
+        InstructionDisplay {
+            insn: self,
+            opts,
+        }
+    }
+}
+
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.display(&FormatOptions::default()))
+    }
+}
+
+impl<'a> fmt::Display for InstructionDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let insn = self.insn;
 
         // How many characters wide is the byte dump?
-        // (At least 2)
-        let mut bytes_width: i32 = 2;
+        let mut bytes_width = insn.opcode_len() * 2;
 
         // Print instruction bytes
-        write!(f, "{:02x}", self.opcode)?;
+        write!(f, "{:02x}", insn.opcode)?;
 
-        for i in 0..self.operand_count as usize {
-            let op: &Operand = &self.operands[i];
+        for i in 0..insn.operand_count as usize {
+            let op: &Operand = &insn.operands[i];
+
+            if self.opts.group_bytes && op.cursor > 0 {
+                write!(f, " |")?;
+                bytes_width += 2;
+            }
 
             for j in 0..op.cursor {
                 write!(f, " {:02x}", op.bytes[j])?;
@@ -204,20 +750,24 @@ impl fmt::Display for Instruction {
             }
         }
 
-        // Now compute how many spaces we need to write.
-
-        let spaces_needed: i32 = 30 - bytes_width;
-
-        if spaces_needed > 0 {
-            for _ in 0..spaces_needed {
-                write!(f, " ")?;
-            }
+        // Pad out to the mnemonic column. A byte dump wider than the
+        // column simply pushes the mnemonic to the right.
+        for _ in bytes_width..self.opts.pad_width {
+            write!(f, " ")?;
         }
 
         // Now write the mnemonic
-        write!(f, " | {}", self.name)?;
+        write!(f, " | ")?;
+
+        for c in insn.name.chars() {
+            let c = match self.opts.case {
+                Case::Upper => c.to_ascii_uppercase(),
+                Case::Lower => c.to_ascii_lowercase(),
+            };
+            write!(f, "{}", c)?;
+        }
 
-        let mut more_spaces: i32 = 10 - self.name.len() as i32;
+        let more_spaces: i32 = 10 - insn.name.len() as i32;
 
         if more_spaces > 0 {
             for _ in 0..more_spaces {
@@ -225,16 +775,82 @@ impl fmt::Display for Instruction {
             }
         }
 
-        let op_count = self.operand_count as usize;
+        // Operands are written in decode order, which matches the
+        // assembler's source-first, destination-last convention.
+        let op_count = insn.operand_count as usize;
 
         for i in 0..op_count {
-            write!(f, "{}", self.operands[i])?;
+            write!(f, "{}", insn.operands[i].display(self.opts.reg_style))?;
             if i < op_count - 1 {
                 write!(f, ",")?;
             }
         }
 
-        return Ok(())
+        if self.opts.verbosity >= 2 {
+            if let Some(note) = insn.implicit_operands() {
+                write!(f, "  ; {}", note)?;
+            }
+
+            // The assembler writes an address-of source the same way as
+            // a memory operand, so say what is really moved.
+            if insn.takes_address() && op_count > 0 {
+                write!(f, "  ; address of {}", insn.operands[0].display(self.opts.reg_style))?;
+            }
+
+            // ROTW rotates right by the low five bits of its count; there
+            // is no left rotate and the count's sign is ignored.
+            if insn.opcode == 0xD8 && op_count > 0 {
+                let count = &insn.operands[0];
+
+                match count.mode {
+                    AddrMode::PositiveLiteral | AddrMode::NegativeLiteral |
+                    AddrMode::ByteImmediate | AddrMode::HalfwordImmediate |
+                    AddrMode::WordImmediate => {
+                        write!(f, "  ; rotate right by {}", count.embedded & 0x1f)?;
+                    }
+                    _ => write!(f, "  ; rotate right by count mod 32")?,
+                }
+            }
+
+            if let Some(flags) = insn.flag_effects() {
+                write!(f, "  ; sets {}", flags)?;
+            }
+
+            // Writing %psw changes far more than the condition flags.
+            if insn.operands[..op_count].iter().any(|op| op.mode == AddrMode::Register && op.register == Some(11)) {
+                write!(f, "  ; %psw is the processor status word")?;
+            }
+
+            if insn.is_in_place() {
+                write!(f, "  ; in-place")?;
+            }
+
+            if let Some(condition) = insn.condition() {
+                write!(f, "  ; taken if {} ({})", condition.description(), condition.flag_test())?;
+            }
+
+            if let Some(note) = insn.semantics_note() {
+                write!(f, "  ; {}", note)?;
+            }
+
+            match insn.stack_effect() {
+                Some(bytes) if bytes < 0 => write!(f, "  ; sp -= {}", -bytes)?,
+                Some(bytes) => write!(f, "  ; sp += {}", bytes)?,
+                None => {}
+            }
+        }
+
+        if self.opts.verbosity >= 3 {
+            let mut first = true;
+
+            for desc in insn.operands[..op_count].iter().filter_map(Operand::descriptor) {
+                write!(f, "{} {:02x}=m{},r{}", if first { "  ; desc" } else { "," },
+                       desc, desc >> 4, desc & 0xf)?;
+                first = false;
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -250,8 +866,12 @@ macro_rules! mn {
     };
 }
 
+// Operand types are listed in the order their bytes appear in the
+// instruction stream. This is also the order in which the WE32100
+// assembler writes them, with the destination always last, e.g.
+// "ADDW3 src1,src2,dst" or "INSFW width,offset,src,dst".
 static BYTE_MNEMONICS: [Option<Mnemonic>; 256] = [
-    Some(mn!(0x00, Data::None, "halt", [OpType::None, OpType::None, OpType::None, OpType::None])),
+    Some(mn!(0x00, Data::None, "HALT", [OpType::None, OpType::None, OpType::None, OpType::None])),
     None,
     Some(mn!(0x02, Data::Word, "SPOPRD", [OpType::Lit, OpType::Src, OpType::None, OpType::None])),
     Some(mn!(0x03, Data::Word, "SPOPRD2", [OpType::Lit, OpType::Src, OpType::Dest, OpType::None])),
@@ -509,7 +1129,10 @@ static BYTE_MNEMONICS: [Option<Mnemonic>; 256] = [
     Some(mn!(0xFF, Data::Byte, "SUBB3", [OpType::Src, OpType::Src, OpType::Dest, OpType::None]))
 ];
 
-
+/// The two-byte opcodes, which all begin with 0x30. Entries must be in
+/// ascending order of opcode with no two the same, since the decoder
+/// takes the first match; `tables_are_consistent` enforces this when
+/// the crate is built.
 static HALFWORD_MNEMONICS: [Option<Mnemonic>; HALFWORD_MNEMONIC_COUNT] = [
     Some(mn!(0x3009, Data::None, "MVERNO", [OpType::None, OpType::None, OpType::None, OpType::None])),
     Some(mn!(0x300d, Data::None, "ENBVJMP", [OpType::None, OpType::None, OpType::None, OpType::None])),
@@ -526,8 +1149,220 @@ static HALFWORD_MNEMONICS: [Option<Mnemonic>; HALFWORD_MNEMONIC_COUNT] = [
 
 static NULL_MNEMONIC: Option<Mnemonic> = None;
 
+/// Check that every byte table entry is in the slot for its opcode, and
+/// that the halfword table is in strictly ascending order of opcode, so
+/// that no opcode has two entries and a table edit can't silently make
+/// one unreachable.
+const fn tables_are_consistent() -> bool {
+    let mut i = 0;
+    while i < BYTE_MNEMONICS.len() {
+        if let Some(m) = &BYTE_MNEMONICS[i] {
+            if m.opcode as usize != i {
+                return false;
+            }
+        }
+        i += 1;
+    }
+
+    let mut prev = 0x3000;
+    let mut i = 0;
+    while i < HALFWORD_MNEMONICS.len() {
+        if let Some(m) = &HALFWORD_MNEMONICS[i] {
+            if m.opcode <= prev || m.opcode > 0x30ff {
+                return false;
+            }
+            prev = m.opcode;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+const _: () = assert!(tables_are_consistent(), "duplicate or misplaced opcode in the decode tables");
+
+/// Find a Mnemonic by name, searching the byte table and then the
+/// halfword table. The comparison is case-insensitive.
+fn find_mnemonic(name: &str) -> Option<&'static Mnemonic> {
+    BYTE_MNEMONICS.iter()
+        .chain(HALFWORD_MNEMONICS.iter())
+        .filter_map(|m| m.as_ref())
+        .find(|m| m.name.eq_ignore_ascii_case(name))
+}
+
+/// Look up the opcode for a mnemonic name, e.g. "MOVW" => 0x84.
+///
+/// The lookup is case-insensitive. A few names (e.g. "BNEH" and "BEH")
+/// appear more than once in the decode tables; for those, the first
+/// match in opcode order is returned.
+pub fn opcode_for_mnemonic(name: &str) -> Option<u16> {
+    find_mnemonic(name).map(|m| m.opcode)
+}
+
+/// Look up the operand template for a mnemonic name, e.g. "MOVW" =>
+/// [Src, Dest, None, None]. Follows the same rules as `opcode_for_mnemonic`.
+pub fn operands_for_mnemonic(name: &str) -> Option<[OpType; 4]> {
+    find_mnemonic(name).map(|m| m.ops)
+}
+
+/// One entry of the decode tables, as returned by `opcode_table`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OpcodeEntry {
+    /// The opcode stored in the entry. For the byte table this should
+    /// equal the slot it is in.
+    pub opcode: u16,
+    pub name: &'static str,
+    pub data_type: Data,
+    pub operands: [OpType; 4],
+}
+
+/// Every slot of the decode tables, in opcode order: the 256 slots of
+/// the byte table, each with its entry or `None` if unassigned,
+/// followed by the halfword (0x30-prefixed) opcodes.
+pub fn opcode_table() -> impl Iterator<Item = (u16, Option<OpcodeEntry>)> {
+    let entry = |m: &Mnemonic| OpcodeEntry {
+        opcode: m.opcode,
+        name: m.name,
+        data_type: m.dtype,
+        operands: m.ops,
+    };
+
+    let bytes = BYTE_MNEMONICS.iter()
+        .enumerate()
+        .map(move |(slot, m)| (slot as u16, m.as_ref().map(entry)));
+
+    let halfwords = HALFWORD_MNEMONICS.iter()
+        .filter_map(|m| m.as_ref())
+        .map(move |m| (m.opcode, Some(entry(m))));
+
+    bytes.chain(halfwords)
+}
+
+/// The opcodes the WE32100 defines, by instruction group, for checking
+/// the decode tables against. It is kept separately from the tables so
+/// that a missing or misplaced entry shows up as a difference; any
+/// opcode not here is unassigned, and executing it traps.
+///
+/// The conditional branches on equality appear twice, as the
+/// processor has a pair of opcodes for each (see `Instruction::condition`).
+pub static REFERENCE_OPCODES: &[(u16, &str)] = &[
+    (0x00, "HALT"), (0x70, "NOP"), (0x72, "NOP3"), (0x73, "NOP2"),
+    (0x2e, "BPT"), (0x2f, "WAIT"), (0x14, "EXTOP"), (0x27, "CFLUSH"),
+
+    (0x84, "MOVW"), (0x86, "MOVH"), (0x87, "MOVB"),
+    (0x04, "MOVAW"), (0xe0, "PUSHAW"), (0xa0, "PUSHW"), (0x20, "POPW"),
+    (0x80, "CLRW"), (0x82, "CLRH"), (0x83, "CLRB"),
+    (0x88, "MCOMW"), (0x8a, "MCOMH"), (0x8b, "MCOMB"),
+    (0x8c, "MNEGW"), (0x8e, "MNEGH"), (0x8f, "MNEGB"),
+    (0x1c, "SWAPWI"), (0x1e, "SWAPHI"), (0x1f, "SWAPBI"),
+    (0x0c, "MOVTRW"),
+
+    (0x28, "TSTW"), (0x2a, "TSTH"), (0x2b, "TSTB"),
+    (0x3c, "CMPW"), (0x3e, "CMPH"), (0x3f, "CMPB"),
+    (0x38, "BITW"), (0x3a, "BITH"), (0x3b, "BITB"),
+
+    (0x90, "INCW"), (0x92, "INCH"), (0x93, "INCB"),
+    (0x94, "DECW"), (0x96, "DECH"), (0x97, "DECB"),
+    (0x9c, "ADDW2"), (0x9e, "ADDH2"), (0x9f, "ADDB2"),
+    (0xdc, "ADDW3"), (0xde, "ADDH3"), (0xdf, "ADDB3"),
+    (0xbc, "SUBW2"), (0xbe, "SUBH2"), (0xbf, "SUBB2"),
+    (0xfc, "SUBW3"), (0xfe, "SUBH3"), (0xff, "SUBB3"),
+    (0xa8, "MULW2"), (0xaa, "MULH2"), (0xab, "MULB2"),
+    (0xe8, "MULW3"), (0xea, "MULH3"), (0xeb, "MULB3"),
+    (0xac, "DIVW2"), (0xae, "DIVH2"), (0xaf, "DIVB2"),
+    (0xec, "DIVW3"), (0xee, "DIVH3"), (0xef, "DIVB3"),
+    (0xa4, "MODW2"), (0xa6, "MODH2"), (0xa7, "MODB2"),
+    (0xe4, "MODW3"), (0xe6, "MODH3"), (0xe7, "MODB3"),
+    (0xb8, "ANDW2"), (0xba, "ANDH2"), (0xbb, "ANDB2"),
+    (0xf8, "ANDW3"), (0xfa, "ANDH3"), (0xfb, "ANDB3"),
+    (0xb0, "ORW2"), (0xb2, "ORH2"), (0xb3, "ORB2"),
+    (0xf0, "ORW3"), (0xf2, "ORH3"), (0xf3, "ORB3"),
+    (0xb4, "XORW2"), (0xb6, "XORH2"), (0xb7, "XORB2"),
+    (0xf4, "XORW3"), (0xf6, "XORH3"), (0xf7, "XORB3"),
+
+    (0xc0, "ALSW3"), (0xc4, "ARSW3"), (0xc6, "ARSH3"), (0xc7, "ARSB3"),
+    (0xd0, "LLSW3"), (0xd2, "LLSH3"), (0xd3, "LLSB3"), (0xd4, "LRSW3"),
+    (0xd8, "ROTW"),
+    (0xcc, "EXTFW"), (0xce, "EXTFH"), (0xcf, "EXTFB"),
+    (0xc8, "INSFW"), (0xca, "INSFH"), (0xcb, "INSFB"),
+
+    (0x7a, "BRH"), (0x7b, "BRB"), (0x24, "JMP"),
+    (0x36, "BSBH"), (0x37, "BSBB"), (0x34, "JSB"), (0x78, "RSB"),
+    (0x2c, "CALL"), (0x10, "SAVE"), (0x18, "RESTORE"), (0x08, "RET"),
+    (0x6e, "BEH"), (0x6f, "BEB"), (0x7e, "BEH"), (0x7f, "BEB"),
+    (0x66, "BNEH"), (0x67, "BNEB"), (0x76, "BNEH"), (0x77, "BNEB"),
+    (0x46, "BGH"), (0x47, "BGB"), (0x42, "BGEH"), (0x43, "BGEB"),
+    (0x4a, "BLH"), (0x4b, "BLB"), (0x4e, "BLEH"), (0x4f, "BLEB"),
+    (0x56, "BGUH"), (0x57, "BGUB"), (0x52, "BGEUH"), (0x53, "BGEUB"),
+    (0x5a, "BLUH"), (0x5b, "BLUB"), (0x5e, "BLEUH"), (0x5f, "BLEUB"),
+    (0x62, "BVCH"), (0x63, "BVCB"), (0x6a, "BVSH"), (0x6b, "BVSB"),
+    (0x7c, "REQL"), (0x6c, "REQLU"), (0x74, "RNEQ"), (0x64, "RNEQU"),
+    (0x44, "RGTR"), (0x54, "RGTRU"), (0x40, "RGEQ"), (0x50, "RGEQU"),
+    (0x48, "RLSS"), (0x58, "RLSSU"), (0x4c, "RLEQ"), (0x5c, "RLEQU"),
+    (0x60, "RVC"), (0x68, "RVS"),
+
+    (0x32, "SPOP"), (0x22, "SPOPRS"), (0x02, "SPOPRD"), (0x06, "SPOPRT"),
+    (0x23, "SPOPS2"), (0x03, "SPOPRD2"), (0x07, "SPOPT2"),
+    (0x33, "SPOPWS"), (0x13, "SPOPWD"), (0x17, "SPOPWT"),
+
+    (0x3009, "MVERNO"), (0x300d, "ENBVJMP"), (0x3013, "DISVJMP"),
+    (0x3019, "MOVBLW"), (0x301f, "STREND"), (0x303f, "STRCPY"),
+    (0x302f, "INTACK"), (0x3045, "RETG"), (0x3061, "GATE"),
+    (0x30ac, "CALLPS"), (0x30c8, "RETPS"),
+];
+
+/// Check the decode tables for a mnemonic with a literal operand but
+/// no byte, halfword or word data type to give the literal's size.
+/// Returns the name of the first such mnemonic, or `None` if the
+/// tables are consistent.
+pub fn find_unsized_literal() -> Option<&'static str> {
+    BYTE_MNEMONICS.iter()
+        .chain(HALFWORD_MNEMONICS.iter())
+        .filter_map(|m| m.as_ref())
+        .filter(|m| m.ops.contains(&OpType::Lit))
+        .find(|m| !matches!(m.dtype, Data::Byte | Data::Half | Data::Word))
+        .map(|m| m.name)
+}
+
+/// A minimal little-endian reader over a byte slice, so that the
+/// decoder does not depend on std::io.
+struct SliceCursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SliceCursor<'a> {
+    fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        let b = *self.buf.get(self.pos).ok_or(DecodeError::Truncated)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, DecodeError> {
+        let lo = self.read_u8()?;
+        let hi = self.read_u8()?;
+        Ok(u16::from(lo) | (u16::from(hi) << 8))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        let lo = self.read_u16()?;
+        let hi = self.read_u16()?;
+        Ok(u32::from(lo) | (u32::from(hi) << 16))
+    }
+}
+
+/// The longest an instruction can be: a one-byte opcode and four
+/// operands, each an expanded-type byte, a descriptor and a word.
+/// Real code comes nowhere near this, but a lower default would
+/// reject valid instructions: a MOVW between two absolute addresses
+/// is already 11 bytes, and INSFW and EXTFW take four operands.
+pub const MAX_INSTRUCTION_LEN: usize = 25;
+
 pub struct Decoder {
     pub ir: Instruction,
+    /// The most bytes one instruction may take. An instruction that
+    /// decodes to more is rejected with `DecodeError::TooLong`.
+    pub max_len: usize,
 }
 
 impl Default for Decoder {
@@ -550,19 +1385,30 @@ impl Decoder {
                     Operand::new(0, AddrMode::None, Data::None, None, None, 0),
                     Operand::new(0, AddrMode::None, Data::None, None, None, 0),
                 ]
-            }
+            },
+            max_len: MAX_INSTRUCTION_LEN,
         }
     }
 
+    /// Return `ir` to the "???" state of a new Decoder, keeping the
+    /// length limit. Decoding an instruction overwrites all of `ir`
+    /// anyway, so this is only needed for a clean point to inspect,
+    /// e.g. when reusing a Decoder for another file or section.
+    pub fn reset(&mut self) {
+        self.ir = Decoder::new().ir;
+    }
+
     /// Decode a literal Operand type.
     ///
     /// These operands belong to only certain instructions, where a word without
     /// a descriptor byte immediately follows the opcode.
-    fn decode_literal_operand(&mut self, cursor: &mut Cursor<&[u8]>, index: usize, mn: &Mnemonic) -> Result<(), DecodeError> {
+    fn decode_literal_operand(&mut self, cursor: &mut SliceCursor, index: usize, mn: &Mnemonic) -> Result<(), DecodeError> {
         let op = &mut self.ir.operands[index];
 
+        // The literal's size is given by the mnemonic's data type, so
+        // e.g. BSBB and BRB take a byte and BSBH and BRH a halfword.
         op.mode = AddrMode::None;
-        op.data_type = Data::Byte;
+        op.data_type = mn.dtype;
         op.expanded_type = None;
         op.register = None;
 
@@ -573,16 +1419,17 @@ impl Decoder {
                 op.append_u8(b);
             }
             Data::Half => {
-                let h: u16 = cursor.read_u16::<LittleEndian>()?;
+                let h: u16 = cursor.read_u16()?;
                 op.embedded = u32::from(h);
                 op.append_u16(h);
             }
             Data::Word => {
-                let w: u32 = cursor.read_u32::<LittleEndian>()?;
+                let w: u32 = cursor.read_u32()?;
                 op.embedded = w;
                 op.append_u32(w);
             }
-            _ => return Err(DecodeError::Parse),
+            // Only a mistake in the decode tables can get here.
+            _ => return Err(DecodeError::BadLiteralType(mn.name)),
         }
 
         Ok(())
@@ -591,7 +1438,7 @@ impl Decoder {
     /// Decode a descriptor Operand type.
     fn decode_descriptor_operand(
         &mut self,
-        cursor: &mut Cursor<&[u8]>,
+        cursor: &mut SliceCursor,
         index: usize,
         dtype: Data,
         etype: Option<Data>,
@@ -601,6 +1448,7 @@ impl Decoder {
 
         op.data_type = dtype;
         op.expanded_type = etype;
+        op.typed = recur;
 
         let descriptor_byte: u8 = cursor.read_u8()?;
 
@@ -610,7 +1458,7 @@ impl Decoder {
         let r = descriptor_byte & 0xf;
 
         match m {
-            0 | 1 | 2 | 3 => {
+            0..=3 => {
                 // Positive Literal
                 op.mode = AddrMode::PositiveLiteral;
                 op.register = None;
@@ -620,8 +1468,9 @@ impl Decoder {
                 match r {
                     15 => {
                         // Word Immediate
-                        let w = cursor.read_u32::<LittleEndian>()?;
+                        let w = cursor.read_u32()?;
                         op.mode = AddrMode::WordImmediate;
+                        op.data_type = Data::Word;
                         op.register = None;
                         op.embedded = w;
                         op.append_u32(w);
@@ -638,8 +1487,9 @@ impl Decoder {
                 match r {
                     15 => {
                         // Halfword Immediate
-                        let h = cursor.read_u16::<LittleEndian>()?;
+                        let h = cursor.read_u16()?;
                         op.mode = AddrMode::HalfwordImmediate;
+                        op.data_type = Data::Half;
                         op.register = None;
                         op.embedded = u32::from(h);
                         op.append_u16(h);
@@ -662,6 +1512,7 @@ impl Decoder {
                         // Byte Immediate
                         let b = cursor.read_u8()?;
                         op.mode = AddrMode::ByteImmediate;
+                        op.data_type = Data::Byte;
                         op.register = None;
                         op.embedded = u32::from(b);
                         op.append_u8(b);
@@ -678,7 +1529,7 @@ impl Decoder {
                 match r {
                     15 => {
                         // Absolute
-                        let w = cursor.read_u32::<LittleEndian>()?;
+                        let w = cursor.read_u32()?;
                         op.mode = AddrMode::Absolute;
                         op.register = None;
                         op.embedded = w;
@@ -697,7 +1548,7 @@ impl Decoder {
                     11 => return Err(DecodeError::Parse),
                     _ => {
                         // Word Displacement
-                        let disp = cursor.read_u32::<LittleEndian>()?;
+                        let disp = cursor.read_u32()?;
                         op.mode = AddrMode::WordDisplacement;
                         op.register = Some(r as usize);
                         op.embedded = disp;
@@ -710,7 +1561,7 @@ impl Decoder {
                     11 => return Err(DecodeError::Parse),
                     _ => {
                         // Word Displacement Deferred
-                        let disp = cursor.read_u32::<LittleEndian>()?;
+                        let disp = cursor.read_u32()?;
                         op.mode = AddrMode::WordDisplacementDeferred;
                         op.register = Some(r as usize);
                         op.embedded = disp;
@@ -723,7 +1574,7 @@ impl Decoder {
                     11 => return Err(DecodeError::Parse),
                     _ => {
                         // Halfword Displacement
-                        let disp = cursor.read_u16::<LittleEndian>()?;
+                        let disp = cursor.read_u16()?;
                         op.mode = AddrMode::HalfwordDisplacement;
                         op.register = Some(r as usize);
                         op.embedded = u32::from(disp);
@@ -736,7 +1587,7 @@ impl Decoder {
                     11 => return Err(DecodeError::Parse),
                     _ => {
                         // Halfword Displacement Deferred
-                        let disp = cursor.read_u16::<LittleEndian>()?;
+                        let disp = cursor.read_u16()?;
                         op.mode = AddrMode::HalfwordDisplacementDeferred;
                         op.register = Some(r as usize);
                         op.embedded = u32::from(disp);
@@ -771,6 +1622,9 @@ impl Decoder {
                 }
             }
             14 => match r {
+                // An expanded-type byte applies to the descriptor that
+                // follows it, which may not be another one.
+                0 | 2 | 3 | 4 | 6 | 7 if recur => return Err(DecodeError::Parse),
                 0 => self.decode_descriptor_operand(cursor, index, dtype, Some(Data::UWord), true)?,
                 2 => self.decode_descriptor_operand(cursor, index, dtype, Some(Data::UHalf), true)?,
                 3 => self.decode_descriptor_operand(cursor, index, dtype, Some(Data::Byte), true)?,
@@ -778,7 +1632,7 @@ impl Decoder {
                 6 => self.decode_descriptor_operand(cursor, index, dtype, Some(Data::Half), true)?,
                 7 => self.decode_descriptor_operand(cursor, index, dtype, Some(Data::SByte), true)?,
                 15 => {
-                    let w = cursor.read_u32::<LittleEndian>()?;
+                    let w = cursor.read_u32()?;
                     op.mode = AddrMode::AbsoluteDeferred;
                     op.register = None;
                     op.embedded = w;
@@ -801,14 +1655,15 @@ impl Decoder {
     /// Fully decode an Operand
     fn decode_operand(
         &mut self,
-        cursor: &mut Cursor<&[u8]>,
+        cursor: &mut SliceCursor,
         index: usize,
         mn: &Mnemonic,
         ot: OpType,
         etype: Option<Data>,
     ) -> Result<(), DecodeError> {
-
-        self.ir.operands[index].reset();
+        // Recorded here rather than in decode_descriptor_operand, which
+        // calls itself after reading an expanded-type byte.
+        self.ir.operands[index].offset = cursor.pos;
 
         match ot {
             OpType::Lit => self.decode_literal_operand(cursor, index, mn),
@@ -817,8 +1672,47 @@ impl Decoder {
         }
     }
 
-    /// Decode the instruction currently pointed at by the cursor.
+    /// Decode the instruction currently pointed at by the cursor, and
+    /// advance the cursor past it.
+    #[cfg(feature = "std")]
     pub fn decode_instruction(&mut self, cursor: &mut Cursor<&[u8]>) -> Result<(), DecodeError> {
+        let offset = cursor.position() as usize;
+        let len = self.decode_at(cursor.get_ref(), offset)?;
+        cursor.set_position((offset + len) as u64);
+        Ok(())
+    }
+
+    /// Decode the instruction starting at `offset` in `buf`, returning
+    /// the number of bytes it occupies.
+    ///
+    /// This is the core of the decoder. It depends only on `core`, so
+    /// it is available without the "std" feature.
+    pub fn decode_at(&mut self, buf: &[u8], offset: usize) -> Result<usize, DecodeError> {
+        let mut cursor = SliceCursor {
+            buf,
+            pos: offset,
+        };
+
+        self.decode(&mut cursor)?;
+
+        Ok(cursor.pos - offset)
+    }
+
+    /// Fail with `DecodeError::TooLong` if the instruction that began
+    /// at `start` has already run past the length limit.
+    fn check_len(&self, cursor: &SliceCursor, start: usize) -> Result<(), DecodeError> {
+        let len = cursor.pos - start;
+
+        if len > self.max_len {
+            return Err(DecodeError::TooLong(len));
+        }
+
+        Ok(())
+    }
+
+    fn decode(&mut self, cursor: &mut SliceCursor) -> Result<(), DecodeError> {
+        let start = cursor.pos;
+
         // Read the first byte of the instruction. Most instructions are only
         // one byte, so this is usually enough.
         let b1 = cursor.read_u8()?;
@@ -828,34 +1722,47 @@ impl Decoder {
         // we're reading is a halfword, requiring two bytes.
 
         let mut mn: &Option<Mnemonic> = &NULL_MNEMONIC;
+        let mut opcode = u16::from(b1);
 
         if b1 == 0x30 {
             let b2 = cursor.read_u8()?;
 
-            let opcode = (u16::from(b1) << 8) | u16::from(b2);
+            opcode = (u16::from(b1) << 8) | u16::from(b2);
 
-            for m in &HALFWORD_MNEMONICS {
-                if m.is_some() && m.as_ref().unwrap().opcode == opcode {
-                    mn = m;
-                    break;
-                }
+            // Opcodes in this table are unique (see
+            // tables_are_consistent), so the first match is the only one.
+            if let Some(m) = HALFWORD_MNEMONICS.iter()
+                .find(|m| matches!(m, Some(m) if m.opcode == opcode)) {
+                mn = m;
             }
         } else {
             mn = &BYTE_MNEMONICS[b1 as usize];
         };
 
+        self.check_len(cursor, start)?;
+
         // If we found a valid mnemonic, read in and decode all of its operands.
         match mn {
             Some(mn) => {
                 let mut etype: Option<Data> = None;
                 let mut index: usize = 0;
 
+                // Reset all four operands, including any this
+                // instruction doesn't use, so that none of them carries
+                // state over from the previous instruction.
+                for op in self.ir.operands.iter_mut() {
+                    op.reset();
+                }
+
                 for ot in &mn.ops {
                     if *ot == OpType::None {
                         break;
                     }
                     // Push a decoded operand
                     self.decode_operand(cursor, index, mn, *ot, etype)?;
+                    // Checked as each operand is read, so that a run of
+                    // bad descriptors is given up on as soon as possible.
+                    self.check_len(cursor, start)?;
                     etype = self.ir.operands[index].expanded_type;
                     index += 1;
                 }
@@ -865,9 +1772,285 @@ impl Decoder {
                 self.ir.operand_count = index as u8;
                 self.ir.data_type = mn.dtype;
             }
-            None => return Err(DecodeError::Parse),
+            None => return Err(DecodeError::UnknownOpcode(opcode)),
         }
 
         Ok(())
     }
 }
+
+/// Decode the instruction at the start of `bytes` and return it. A
+/// convenience for tests and one-off lookups, where setting up a
+/// Decoder is just boilerplate.
+pub fn decode_one(bytes: &[u8]) -> Result<Instruction, DecodeError> {
+    let mut decoder = Decoder::new();
+    decoder.decode_at(bytes, 0)?;
+    Ok(decoder.ir)
+}
+
+/// Decode `buf` linearly from the start and check that every
+/// instruction re-encodes to exactly the bytes it was decoded from.
+/// Returns the number of instructions checked, or the offset of the
+/// first instruction that does not round-trip. Decoding stops at the
+/// first byte sequence that is not a valid instruction.
+#[cfg(feature = "std")]
+pub fn verify_round_trip(buf: &[u8]) -> Result<usize, usize> {
+    let mut decoder = Decoder::new();
+    let mut offset = 0;
+    let mut count = 0;
+
+    while let Ok(len) = decoder.decode_at(buf, offset) {
+        if decoder.ir.encode() != buf[offset..offset + len] {
+            return Err(offset);
+        }
+
+        offset += len;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    /// Decode one instruction that is expected to be valid.
+    fn decode(bytes: &[u8]) -> Instruction {
+        decode_one(bytes).unwrap_or_else(|e| panic!("{:02x?}: {}", bytes, e))
+    }
+
+    /// An instruction's operands as the listing shows them.
+    fn operands(ir: &Instruction, style: RegStyle) -> String {
+        let operands: Vec<String> = ir.operands[..ir.operand_count as usize]
+            .iter()
+            .map(|op| op.display(style).to_string())
+            .collect();
+
+        operands.join(",")
+    }
+
+    #[test]
+    fn register_styles() {
+        // ADDW3 4(%fp),%psw,(%sp)
+        let ir = decode(&[0xdc, 0x64, 0x4b, 0x5c]);
+
+        let cases = [
+            (RegStyle::Special, "4(%fp),%psw,(%sp)"),
+            (RegStyle::Numeric, "4(%r9),%r11,(%r12)"),
+            (RegStyle::Bare, "4(fp),psw,(sp)"),
+        ];
+
+        for (style, expected) in &cases {
+            assert_eq!(operands(&ir, *style), *expected, "{:?}", style);
+        }
+    }
+
+    #[test]
+    fn address_of_note_follows_register_style() {
+        // MOVAW 8(%fp),%r0
+        let ir = decode(&[0x04, 0xc9, 0x08, 0x40]);
+
+        for (style, expected) in &[(RegStyle::Special, "8(%fp)"), (RegStyle::Numeric, "8(%r9)")] {
+            let opts = FormatOptions { reg_style: *style, verbosity: 2, ..FormatOptions::default() };
+            let text = ir.display(&opts).to_string();
+            let note = std::format!("; address of {}", expected);
+
+            assert!(text.contains(&note), "{:?}: {}", style, text);
+        }
+
+        // Notes are only written at -vv and above.
+        let text = ir.display(&FormatOptions::default()).to_string();
+        assert!(!text.contains(';'), "{}", text);
+    }
+
+    #[test]
+    fn operand_offsets() {
+        // NOP, then ADDW3 &5,%r0,4(%fp)
+        let buf = [0x70, 0xdc, 0x6f, 0x05, 0x40, 0x64];
+
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.decode_at(&buf, 1).unwrap(), 5);
+
+        let ops = &decoder.ir.operands;
+        assert_eq!([ops[0].offset(), ops[1].offset(), ops[2].offset()], [2, 4, 5]);
+    }
+
+    #[test]
+    fn conditions() {
+        // Both BNEB opcodes are here, and both returns on equal.
+        let cases: &[(&[u8], Condition)] = &[
+            (&[0x43, 0x10], Condition::GreaterOrEqual),
+            (&[0x4b, 0x10], Condition::Less),
+            (&[0x53, 0x10], Condition::GreaterOrEqualUnsigned),
+            (&[0x5e, 0x10, 0x00], Condition::LessOrEqualUnsigned),
+            (&[0x63, 0x10], Condition::OverflowClear),
+            (&[0x67, 0x10], Condition::NotEqual),
+            (&[0x77, 0x10], Condition::NotEqual),
+            (&[0x6c], Condition::Equal),
+            (&[0x7c], Condition::Equal),
+        ];
+
+        for (bytes, expected) in cases {
+            assert_eq!(decode(bytes).condition(), Some(*expected), "{:02x?}", bytes);
+        }
+    }
+
+    #[test]
+    fn immediate_types() {
+        // Immediates in instructions of another size take the
+        // immediate's own size.
+        let cases: &[(&[u8], Data)] = &[
+            (&[0x84, 0x6f, 0x05, 0x40], Data::Byte),
+            (&[0x84, 0x5f, 0x34, 0x12, 0x40], Data::Half),
+            (&[0x87, 0x4f, 0x78, 0x56, 0x34, 0x12, 0x40], Data::Word),
+        ];
+
+        for (bytes, expected) in cases {
+            assert_eq!(decode(bytes).operands[0].data_type(), *expected, "{:02x?}", bytes);
+        }
+    }
+
+    #[test]
+    fn descriptors() {
+        // MOVW {sbyte}%r1,%r0: the expanded-type byte is not the
+        // descriptor.
+        let ir = decode(&[0x84, 0xe7, 0x41, 0x40]);
+        assert_eq!(ir.operands[0].bytes(), &[0xe7, 0x41]);
+        assert_eq!(ir.operands[0].descriptor(), Some(0x41));
+        assert_eq!(ir.operands[1].descriptor(), Some(0x40));
+
+        // BRB's displacement is a literal, with no descriptor.
+        assert_eq!(decode(&[0x7b, 0x10]).operands[0].descriptor(), None);
+    }
+
+    #[test]
+    fn byte_dump_grouping() {
+        // ADDW3 &5,%r0,%r1, with no padding before the mnemonic
+        let ir = decode(&[0xdc, 0x6f, 0x05, 0x40, 0x41]);
+
+        for (group_bytes, expected) in &[(false, "dc 6f 05 40 41 | ADDW3"), (true, "dc | 6f 05 | 40 | 41 | ADDW3")] {
+            let opts = FormatOptions { pad_width: 0, group_bytes: *group_bytes, ..FormatOptions::default() };
+            let text = ir.display(&opts).to_string();
+
+            assert!(text.starts_with(expected), "group_bytes {}: {}", group_bytes, text);
+        }
+    }
+
+    #[test]
+    fn terminators() {
+        // Conditional returns and branches, and calls, can all carry on
+        // with the next instruction.
+        let cases: &[(&[u8], bool)] = &[
+            (&[0x08], true),
+            (&[0x78], true),
+            (&[0x30, 0x45], true),
+            (&[0x30, 0xc8], true),
+            (&[0x7b, 0x10], true),
+            (&[0x7c], false),
+            (&[0x7f, 0x10], false),
+            (&[0x37, 0x10], false),
+        ];
+
+        for (bytes, expected) in cases {
+            let ir = decode(bytes);
+            assert_eq!(ir.is_terminator(), *expected, "{}", ir.name);
+        }
+    }
+
+    #[test]
+    fn return_notes() {
+        let cases: &[(&[u8], &str)] = &[
+            (&[0x08], "returns from a CALL"),
+            (&[0x78], "returns from a BSB or JSB"),
+            (&[0x30, 0x45], "returns from a GATE"),
+        ];
+
+        let opts = FormatOptions { verbosity: 2, ..FormatOptions::default() };
+
+        for (bytes, expected) in cases {
+            let ir = decode(bytes);
+            assert_eq!(ir.flow(), Flow::Return, "{}", ir.name);
+            assert!(ir.semantics_note().unwrap().starts_with(expected), "{}", ir.name);
+            assert!(ir.display(&opts).to_string().contains(expected), "{}", ir.name);
+        }
+    }
+
+    #[test]
+    fn operand_order() {
+        // One instruction from each operand-count class, written as the
+        // WE32100 assembler writes them: sources first, destination
+        // last, and a field's width before its offset.
+        let cases: &[(&[u8], &str)] = &[
+            (&[0x70], "NOP"),
+            (&[0x7b, 0x10], "BRB 16"),
+            (&[0x80, 0x40], "CLRW %r0"),
+            (&[0x84, 0x41, 0x40], "MOVW %r1,%r0"),
+            (&[0x9c, 0x01, 0x40], "ADDW2 &1,%r0"),
+            (&[0xfc, 0x01, 0x41, 0x42], "SUBW3 &1,%r1,%r2"),
+            (&[0xd0, 0x02, 0x40, 0x41], "LLSW3 &2,%r0,%r1"),
+            (&[0xc8, 0x03, 0x04, 0x40, 0x41], "INSFW &3,&4,%r0,%r1"),
+            (&[0xcc, 0x03, 0x04, 0x40, 0x41], "EXTFW &3,&4,%r0,%r1"),
+        ];
+
+        for (bytes, expected) in cases {
+            let ir = decode(bytes);
+            let text = match operands(&ir, RegStyle::Special) {
+                ops if ops.is_empty() => ir.name.to_string(),
+                ops => std::format!("{} {}", ir.name, ops),
+            };
+
+            assert_eq!(text, *expected);
+        }
+    }
+
+    #[test]
+    fn destination_is_last() {
+        for mn in BYTE_MNEMONICS.iter().chain(HALFWORD_MNEMONICS.iter()).flatten() {
+            let count = mn.ops.iter().take_while(|ot| **ot != OpType::None).count();
+
+            if let Some(dest) = mn.ops[..count].iter().position(|ot| *ot == OpType::Dest) {
+                assert_eq!(dest, count - 1, "{}", mn.name);
+            }
+        }
+    }
+
+    #[test]
+    fn nested_expanded_type_is_rejected() {
+        // MOVW whose source is {uword} applied to another expanded-type
+        // byte, rather than to a descriptor.
+        assert!(matches!(decode_one(&[0x84, 0xe0, 0xe0, 0x40]), Err(DecodeError::Parse)));
+    }
+
+    #[test]
+    fn length_limit_rejects_long_instruction() {
+        // MOVW {uword}$0x1000,{uword}$0x2000
+        let bytes = [
+            0x84,
+            0xe0, 0x7f, 0x00, 0x10, 0x00, 0x00,
+            0xe0, 0x7f, 0x00, 0x20, 0x00, 0x00,
+        ];
+
+        let mut decoder = Decoder { max_len: 10, ..Decoder::new() };
+        assert!(matches!(decoder.decode_at(&bytes, 0), Err(DecodeError::TooLong(13))));
+
+        assert_eq!(Decoder::new().decode_at(&bytes, 0).unwrap(), bytes.len());
+    }
+
+    #[test]
+    fn longest_instruction_is_within_default_limit() {
+        // INSFW with every operand {uword}*$addr
+        let mut bytes = [0; MAX_INSTRUCTION_LEN];
+        bytes[0] = 0xc8;
+        for operand in bytes[1..].chunks_mut(6) {
+            operand.copy_from_slice(&[0xe0, 0xef, 0x00, 0x10, 0x00, 0x00]);
+        }
+
+        assert_eq!(decode(&bytes).operand_count, 4);
+        assert_eq!(Decoder::new().decode_at(&bytes, 0).unwrap(), MAX_INSTRUCTION_LEN);
+    }
+}
@@ -1,63 +1,90 @@
+use core::fmt;
+#[cfg(feature = "std")]
 use std::error;
-use std::fmt;
+#[cfg(feature = "std")]
 use std::io;
+#[cfg(feature = "std")]
+use std::str;
 
+#[cfg(feature = "std")]
 pub type ReadResult<T> = std::result::Result<T, CoffError>;
 
+#[cfg(feature = "std")]
 #[derive(Debug, Clone)]
 pub struct OffsetError;
 
+#[cfg(feature = "std")]
 impl fmt::Display for OffsetError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "bad offset")
     }
 }
 
-impl error::Error for OffsetError {
-    fn description(&self) -> &str {
-        "bad offset"
-    }
+#[cfg(feature = "std")]
+impl error::Error for OffsetError {}
 
-    fn cause(&self) -> Option<&error::Error> {
-        None
-    }
-}
 
-
-#[derive(Debug, Clone)]
+#[cfg(feature = "std")]
+#[derive(Debug)]
 pub enum CoffError {
     BadFileHeader,
     BadOptionalHeader,
     BadSections,
     BadSymbols,
     BadStrings,
+    BadArchive,
+    SectionOutOfBounds {
+        section: String,
+        what: &'static str,
+        offset: u32,
+        len: u64,
+    },
+    IoError(io::Error),
+    Utf8Error(str::Utf8Error),
 }
 
+#[cfg(feature = "std")]
 impl fmt::Display for CoffError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
+        match self {
             CoffError::BadFileHeader => write!(f, "bad file header"),
             CoffError::BadOptionalHeader => write!(f, "bad optional header"),
             CoffError::BadSections => write!(f, "bad section headers"),
             CoffError::BadSymbols => write!(f, "bad symbols table"),
             CoffError::BadStrings => write!(f, "bad strings table"),
+            CoffError::BadArchive => write!(f, "bad archive"),
+            CoffError::SectionOutOfBounds { section, what, offset, len } => {
+                write!(f, "section {} {} at 0x{:x} (0x{:x} bytes) extends past end of file",
+                       section, what, offset, len)
+            }
+            CoffError::IoError(error) => write!(f, "io error on read: {}", error),
+            CoffError::Utf8Error(error) => write!(f, "invalid string: {}", error),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for CoffError {
-    fn description(&self) -> &str {
-        match *self {
-            CoffError::BadFileHeader => "bad file header",
-            CoffError::BadOptionalHeader => "bad file header",
-            CoffError::BadSections => "bad section headers",
-            CoffError::BadSymbols => "bad symbols table",
-            CoffError::BadStrings => "bad strings table",
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CoffError::IoError(error) => Some(error),
+            CoffError::Utf8Error(error) => Some(error),
+            _ => None,
         }
     }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for CoffError {
+    fn from(error: io::Error) -> Self {
+        CoffError::IoError(error)
+    }
+}
 
-    fn cause(&self) -> Option<&error::Error> {
-        None
+#[cfg(feature = "std")]
+impl From<str::Utf8Error> for CoffError {
+    fn from(error: str::Utf8Error) -> Self {
+        CoffError::Utf8Error(error)
     }
 }
 
@@ -66,35 +93,42 @@ impl error::Error for CoffError {
 ///
 #[derive(Debug)]
 pub enum DecodeError {
+    #[cfg(feature = "std")]
     IoError(io::Error),
+    Truncated,
+    UnknownOpcode(u16),
+    BadLiteralType(&'static str),
+    TooLong(usize),
     Parse,
 }
 
 impl fmt::Display for DecodeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            #[cfg(feature = "std")]
             DecodeError::IoError(error) => write!(f, "io error on decode: {:?}", error),
+            DecodeError::Truncated => write!(f, "truncated instruction"),
+            DecodeError::UnknownOpcode(opcode) => write!(f, "unknown opcode 0x{:02x}", opcode),
+            DecodeError::BadLiteralType(name) => write!(f, "literal operand of {} has no size", name),
+            DecodeError::TooLong(len) => write!(f, "instruction of {} bytes is over the length limit", len),
             DecodeError::Parse => write!(f, "parse error on decode"),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for DecodeError {
-    fn description(&self) -> &str {
-        match self {
-            DecodeError::IoError(_) => "io error on decode",
-            DecodeError::Parse => "parse error on decode",
-        }
-    }
-
-    fn cause(&self) -> Option<&error::Error> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             DecodeError::IoError(error) => Some(error),
+            DecodeError::Truncated | DecodeError::UnknownOpcode(_) |
+            DecodeError::BadLiteralType(_) | DecodeError::TooLong(_) |
             DecodeError::Parse => None,
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for DecodeError {
     fn from(error: io::Error) -> Self {
         DecodeError::IoError(error)
@@ -0,0 +1,154 @@
+//!
+//! Control flow analysis over decoded instructions
+//!
+
+use std::collections::BTreeSet;
+use std::io::Cursor;
+
+use crate::decode::{Decoder, Flow, Instruction};
+
+/// How control passes from one basic block to another.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EdgeKind {
+    // Execution runs off the end of the block into the next one
+    FallThrough,
+    // A branch or jump, conditional or not
+    Branch,
+    // A subroutine or procedure call
+    Call,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Edge {
+    pub to: u32,
+    pub kind: EdgeKind,
+}
+
+/// A straight-line run of instructions, entered only at the top and
+/// left only at the bottom (calls aside).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BasicBlock {
+    /// Address of the first instruction
+    pub start: u32,
+    /// Address just past the last instruction
+    pub end: u32,
+    /// Where control can go from this block. Call edges may lead
+    /// outside the data that was decoded.
+    pub edges: Vec<Edge>,
+}
+
+///
+/// The address a branch, jump or call goes to, if it can be known
+/// without running the code: PC-relative branches, and jumps and calls
+/// to an absolute address.
+///
+fn static_target(ir: &Instruction, addr: u32) -> Option<u32> {
+    match ir.flow() {
+        Flow::Branch | Flow::ConditionalBranch | Flow::Call => {
+            ir.branch_target(addr).or_else(|| {
+                let last = (ir.operand_count as usize).checked_sub(1)?;
+                ir.operands[last].effective_address(None)
+            })
+        }
+        _ => None,
+    }
+}
+
+///
+/// Linearly decode `data` from `start`, collecting the addresses of
+/// every branch and call target that falls within the data. `vaddr` is
+/// the load address of the first byte of `data`.
+///
+pub fn branch_targets(data: &[u8], start: usize, vaddr: u32) -> BTreeSet<u32> {
+    let mut targets = BTreeSet::new();
+    let mut decoder = Decoder::new();
+    let mut cursor: Cursor<&[u8]> = Cursor::new(data);
+    cursor.set_position(start as u64);
+
+    let end = vaddr.wrapping_add(data.len() as u32);
+
+    loop {
+        let addr = vaddr.wrapping_add(cursor.position() as u32);
+
+        if decoder.decode_instruction(&mut cursor).is_err() {
+            break;
+        }
+
+        let target = static_target(&decoder.ir, addr);
+
+        if let Some(target) = target {
+            if target >= vaddr && target < end {
+                targets.insert(target);
+            }
+        }
+    }
+
+    targets
+}
+
+///
+/// The name of the synthetic label for an address.
+///
+pub fn label(addr: u32) -> String {
+    format!("L_{:08x}", addr)
+}
+
+///
+/// Linearly decode `data` from `start` and split it into basic blocks.
+/// A block starts at `start`, at every branch target, and after every
+/// instruction that ends a block. `vaddr` is the load address of the
+/// first byte of `data`.
+///
+pub fn basic_blocks(data: &[u8], start: usize, vaddr: u32) -> Vec<BasicBlock> {
+    let targets = branch_targets(data, start, vaddr);
+    let mut blocks: Vec<BasicBlock> = vec!();
+    let mut decoder = Decoder::new();
+    let mut cursor: Cursor<&[u8]> = Cursor::new(data);
+    cursor.set_position(start as u64);
+
+    let mut current: Option<BasicBlock> = None;
+
+    loop {
+        let addr = vaddr.wrapping_add(cursor.position() as u32);
+
+        if decoder.decode_instruction(&mut cursor).is_err() {
+            break;
+        }
+
+        let next = vaddr.wrapping_add(cursor.position() as u32);
+        let ir = &decoder.ir;
+
+        // A branch target in the middle of a block splits it.
+        if targets.contains(&addr) {
+            if let Some(mut block) = current.take() {
+                block.edges.push(Edge { to: addr, kind: EdgeKind::FallThrough });
+                blocks.push(block);
+            }
+        }
+
+        let block = current.get_or_insert_with(|| BasicBlock {
+            start: addr,
+            end: addr,
+            edges: vec!(),
+        });
+
+        block.end = next;
+
+        if let Some(target) = static_target(ir, addr) {
+            let kind = if ir.flow() == Flow::Call { EdgeKind::Call } else { EdgeKind::Branch };
+            block.edges.push(Edge { to: target, kind });
+        }
+
+        if ir.ends_block() {
+            if !ir.is_terminator() {
+                block.edges.push(Edge { to: next, kind: EdgeKind::FallThrough });
+            }
+
+            blocks.extend(current.take());
+        }
+    }
+
+    blocks.extend(current.take());
+
+    blocks
+}
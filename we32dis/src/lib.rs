@@ -0,0 +1,32 @@
+//!
+//! WE32100 disassembler library
+//!
+//! The instruction decoder in `decode` depends only on `core`, as do
+//! `timing` and `psw`. Everything else (COFF parsing, symbols, flow
+//! analysis) requires the default "std" feature.
+//!
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+#[macro_use] extern crate bitflags;
+
+/// The version of this crate, which machine-readable output carries so
+/// that consumers know what produced it.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub mod errors;
+pub mod decode;
+pub mod timing;
+pub mod psw;
+
+#[cfg(feature = "std")]
+pub mod archive;
+#[cfg(feature = "std")]
+pub mod coff;
+#[cfg(feature = "std")]
+pub mod symbols;
+#[cfg(feature = "std")]
+pub mod flow;
+#[cfg(feature = "std")]
+pub mod render;
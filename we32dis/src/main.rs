@@ -1,66 +1,1292 @@
 extern crate clap;
-#[macro_use] extern crate bitflags;
 
-use std::error::Error;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
-use std::vec::Vec;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
-use clap::{Arg, App};
+use clap::{Arg, App, ArgMatches};
 
-use crate::coff::FileContainer;
-use crate::decode::Decoder;
+use we32dis::archive::{self, Archive};
+use we32dis::coff::{FileContainer, NameEncoding, OverlapKind, ReadOptions, SectionFlags, SourceLine,
+                    StringsFormat, TimeFormat};
+use we32dis::decode::{self, AddrMode, Case, Decoder, Flow, FormatOptions, Instruction, OpType, RegStyle, RegisterFile};
+use we32dis::errors::CoffError;
+use we32dis::flow::{self, EdgeKind};
+use we32dis::psw::{execution_level_name, PSW_FIELDS};
+use we32dis::symbols::SymbolMap;
+use we32dis::render::{self, GasRenderer, InstructionRenderer, JsonRenderer, RenderContext, TextRenderer};
+use we32dis::timing;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
 use std::io::Cursor;
+use std::ops::Deref;
 
-mod errors;
-mod coff;
-mod decode;
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    Text,
+    Gas,
+    Json,
+}
 
-fn disassemble(buf: &[u8]) {
-    match FileContainer::read(buf) {
-        Ok(container) => {
-            println!("{:?}", container.header);
-//
-//            if let Some(opt_header) = &container.opt_header {
-//                println!("{:?}", opt_header);
-//            }
-//
-//            for (sec_num, section) in container.sections.iter().enumerate() {
-//                println!("{:?}", section.header);
+///
+/// Where the listing resumes after an undecodable byte in lossy mode.
+/// Every byte that is skipped is shown as data.
+///
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Resync {
+    /// At the next byte.
+    Byte,
+    /// At the next halfword-aligned address.
+    Halfword,
+    /// At the next word-aligned address, where a compiler would start a
+    /// function or a jump table.
+    Word,
+    /// At the next byte from which two instructions in a row decode.
+    /// One decodable instruction is weak evidence in data, since most
+    /// single bytes are valid opcodes; two is a better sign of code.
+    Decodable,
+}
+
+///
+/// Bytes to disassemble, and where they came from.
+///
+#[derive(Clone, Copy)]
+struct Code<'a> {
+    data: &'a [u8],
+    /// Offset in `data` of the first instruction to decode
+    start: usize,
+    /// Load address of `data[0]`
+    vaddr: u32,
+    /// Offset of `data[0]` in the input file
+    file_offset: u64,
+}
+
+// Exit statuses. A file that doesn't parse gives EXIT_PARSE_ERROR, as
+// does, with --validate, one whose section headers overlap; with
+// --validate, a file that parses but has bytes in a text section that
+// don't decode gives EXIT_DECODE_GAPS. Asking for an archive member,
+// function or section the file doesn't have gives EXIT_NOT_FOUND, as
+// does a function in a section that isn't code, without --force. With
+// several files the highest status wins.
+const EXIT_OK: i32 = 0;
+const EXIT_PARSE_ERROR: i32 = 1;
+const EXIT_DECODE_GAPS: i32 = 2;
+const EXIT_NOT_FOUND: i32 = 3;
+
+struct Options {
+    quiet: bool,
+    check: bool,
+    validate: bool,
+    list_sections: bool,
+    map: bool,
+    analyze: bool,
+    symbols_json: bool,
+    summary: bool,
+    identify: bool,
+    strings: Option<StringsFormat>,
+    ascii_strings: Option<Option<String>>,
+    min_len: usize,
+    entry_trace: bool,
+    function: Option<String>,
+    symbolic: bool,
+    blocks: bool,
+    timing: bool,
+    fold_nops: bool,
+    lossy: bool,
+    resync: Resync,
+    force: bool,
+    debug_insn: bool,
+    string_refs: bool,
+    show_offsets: bool,
+    interactive: bool,
+    source_dir: Option<PathBuf>,
+    cfg_dot: bool,
+    max_insn_bytes: usize,
+    extra_symbols: Option<SymbolMap>,
+    symbols_override: bool,
+    time_format: TimeFormat,
+    format: FormatOptions,
+    output: OutputFormat,
+    renderer: Box<dyn InstructionRenderer>,
+}
+
+///
+/// Disassemble or dump a COFF file that starts `origin` bytes into the
+/// input file. Returns the exit status.
+///
+fn disassemble(container: &FileContainer, origin: u64, opts: &Options) -> i32 {
+    if opts.check {
+        check(container, opts.quiet);
+        return EXIT_OK;
+    }
+
+    if opts.map {
+        container.dump_map();
+        return EXIT_OK;
+    }
+
+    if opts.analyze {
+        analyze(container, opts);
+        return EXIT_OK;
+    }
+
+    if opts.symbols_json {
+        container.dump_symbols_json();
+        return EXIT_OK;
+    }
+
+    if opts.list_sections {
+        container.list_sections();
+        return EXIT_OK;
+    }
+
+    if opts.interactive {
+        interactive(container, origin, opts);
+        return EXIT_OK;
+    }
+
+    if let Some(section) = &opts.ascii_strings {
+        if container.dump_ascii_strings(section.as_deref(), opts.min_len).is_err() {
+            eprintln!("No section named {}.", section.as_deref().unwrap_or_default());
+            return EXIT_NOT_FOUND;
+        }
+        return EXIT_OK;
+    }
+
+    if let Some(format) = opts.strings {
+        container.dump_strings(format);
+        return EXIT_OK;
+    }
+
+    if opts.output == OutputFormat::Text && !opts.quiet && !opts.cfg_dot {
+        println!("{}", container.header.display(opts.time_format));
+
+        if let Some(opt_header) = &container.opt_header {
+            println!("{:?}", opt_header);
+        }
+    }
+
+//    for (sec_num, section) in container.sections.iter().enumerate() {
+//        println!("{:?}", section.header);
 //
-//                if let Err(e) = container.dump_relocation_table(sec_num) {
-//                    println!("Error: Couldn't dump relocation table: {:?}", e);
-//                }
+//        if let Err(e) = container.dump_relocation_table(sec_num) {
+//            println!("Error: Couldn't dump relocation table: {:?}", e);
+//        }
 //
-//                if let Err(e) = container.dump_section_data(sec_num) {
-//                    println!("Error: Couldn't dump section data: {:?}", e);
-//                }
-//            }
-//            container.dump_symbol_table();
-//            container.dump_strings_table();
-
-
-            // OK, now let's try to decode some shit.
-            if let Some(data) = container.section_data(0) {
-                println!("\nSection: .text\n");
-                let mut decoder = Decoder::new();
-                let mut cursor: Cursor<&[u8]> = Cursor::new(data);
-
-                while let Ok(()) = decoder.decode_instruction(&mut cursor) {
-                    println!("{}", decoder.ir);
+//        if let Err(e) = container.dump_section_data(sec_num) {
+//            println!("Error: Couldn't dump section data: {:?}", e);
+//        }
+//    }
+//    container.dump_symbol_table();
+//    container.dump_strings_table();
+
+
+    if container.sections.is_empty() {
+        println!("No code sections found.");
+        return EXIT_OK;
+    }
+
+    // Either disassemble a single function, start at the entry point,
+    // or start at the very beginning of the first section.
+    let (sec_num, start, end) = if let Some(name) = &opts.function {
+        match container.function_extent(name) {
+            Some(extent) => extent,
+            None => {
+                eprintln!("Function {} not found.", name);
+                return EXIT_NOT_FOUND;
+            }
+        }
+    } else if opts.entry_trace {
+        match container.entry_point() {
+            Some((sec_num, start)) => (sec_num, start, usize::MAX),
+            None => {
+                eprintln!("Entry point is not in a text section.");
+                return EXIT_NOT_FOUND;
+            }
+        }
+    } else {
+        (0, 0, usize::MAX)
+    };
+
+    // Disassembling data only produces garbage, so make sure that
+    // is what the user wants.
+    if let Some(section) = container.sections.get(sec_num) {
+        if !section.header.section_flags().contains(SectionFlags::STYP_TEXT) && !opts.force {
+            eprintln!("Section {} is not code; use --force to disassemble anyway.",
+                      section.header.name());
+            return EXIT_NOT_FOUND;
+        }
+    }
+
+    let data = match container.try_section_data(sec_num) {
+        Ok(data) => &data[..end.min(data.len())],
+        Err(_) => {
+            eprintln!("No section number {}.", sec_num);
+            return EXIT_NOT_FOUND;
+        }
+    };
+    let header = &container.sections[sec_num].header;
+
+    if data.is_empty() {
+        println!("Section {} has no data.", header.name());
+        return EXIT_OK;
+    }
+
+    if opts.cfg_dot {
+        let name = opts.function.as_deref().unwrap_or_default();
+        print_cfg_dot(name, data, start, header.vaddr, &symbol_map(Some(container), opts));
+        return EXIT_OK;
+    }
+
+    // OK, now let's try to decode some shit.
+    if opts.output == OutputFormat::Text && !opts.quiet {
+        println!("\nSection: {}\n", header.name());
+    }
+    let code = Code {
+        data,
+        start,
+        vaddr: header.vaddr,
+        file_offset: origin + u64::from(header.scnptr),
+    };
+    disassemble_data(&code, &symbol_map(Some(container), opts), Some(container), opts);
+    EXIT_OK
+}
+
+const INTERACTIVE_HELP: &str = "\
+Commands:
+  WHERE [COUNT]   disassemble COUNT instructions (default 1) at WHERE
+  sym WHERE       show the address of a symbol, or the symbol for an address
+  help            show this list
+  quit            leave (as does end of input)
+WHERE is a symbol name or an address, with \"0x\" for hex.";
+
+///
+/// Read commands from stdin that disassemble or look up symbols at
+/// addresses in the file, until "quit" or the end of input.
+///
+fn interactive(container: &FileContainer, origin: u64, opts: &Options) {
+    let symbols = symbol_map(Some(container), opts);
+    let mut decoder = Decoder {
+        max_len: opts.max_insn_bytes,
+        ..Decoder::new()
+    };
+
+    let resolve = |word: &str| -> Option<u32> {
+        symbols.address_of(word).or_else(|| parse_number(word).and_then(|n| u32::try_from(n).ok()))
+    };
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut line = String::new();
+
+    println!("Type an address or symbol to disassemble it, or \"help\".");
+
+    loop {
+        print!("> ");
+        let _ = io::stdout().flush();
+
+        line.clear();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                println!();
+                break;
+            }
+            Ok(_) => {}
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        let (target, count) = match words[..] {
+            [] => continue,
+            ["quit"] | ["q"] | ["exit"] => break,
+            ["help"] | ["?"] => {
+                println!("{}", INTERACTIVE_HELP);
+                continue;
+            }
+            ["sym", word] => {
+                match resolve(word) {
+                    Some(addr) => println!("0x{:08x}  {}", addr, symbols.relative(addr)),
+                    None => println!("No symbol or address {}.", word),
                 }
+                continue;
             }
-        },
-        Err(e) => {
-            println!("Could not parse file: {}", e);
+            [target] => (target, 1),
+            [target, count] => match parse_number(count) {
+                Some(count) => (target, count),
+                None => {
+                    println!("Bad count {}.", count);
+                    continue;
+                }
+            },
+            _ => {
+                println!("Type \"help\" for a list of commands.");
+                continue;
+            }
+        };
+
+        let addr = match resolve(target) {
+            Some(addr) => addr,
+            None => {
+                println!("No symbol or address {}.", target);
+                continue;
+            }
+        };
+
+        let section = container.sections.iter()
+            .filter(|s| !s.data.is_empty())
+            .find(|s| s.address_range().contains(&addr));
+
+        let section = match section {
+            Some(section) => section,
+            None => {
+                println!("0x{:08x} is not in a section with data.", addr);
+                continue;
+            }
+        };
+
+        let mut offset = (addr - section.header.vaddr) as usize;
+        let width = address_width(&opts.format, section.address_range().end.wrapping_sub(1));
+
+        for _ in 0..count {
+            let addr = section.header.vaddr.wrapping_add(offset as u32);
+
+            if offset >= section.data.len() {
+                break;
+            }
+
+            let len = match decoder.decode_at(&section.data, offset) {
+                Ok(len) => len,
+                Err(e) => {
+                    println!("{:0width$x}:  {}", addr, e, width = width);
+                    break;
+                }
+            };
+
+            let ctx = RenderContext {
+                addr,
+                file_offset: origin + u64::from(section.header.scnptr) + offset as u64,
+                symbols: &symbols,
+                format: &opts.format,
+            };
+
+            print!("{:0width$x}:  {}", addr, opts.renderer.render(&decoder.ir, &ctx), width = width);
+
+            if let Some(target) = decoder.ir.branch_target(addr) {
+                print!("  <{}>", symbols.relative(target));
+            }
+
+            println!();
+            offset += len;
+        }
+    }
+}
+
+///
+/// The number of hex digits to print addresses with, for a listing
+/// whose highest address is `last`.
+///
+fn address_width(format: &FormatOptions, last: u32) -> usize {
+    match format.address_width {
+        0 => (32 - last.leading_zeros() as usize).max(1).div_ceil(4),
+        width => width,
+    }
+}
+
+///
+/// Report any problems found in the file's section headers.
+///
+fn check(container: &FileContainer, quiet: bool) {
+    let overlaps = container.overlapping_sections();
+
+    for overlap in &overlaps {
+        let a = &container.sections[overlap.first];
+        let b = &container.sections[overlap.second];
+
+        match overlap.kind {
+            OverlapKind::FileOffset => {
+                let (ra, rb) = (a.file_range(), b.file_range());
+                println!("Sections {} and {} overlap in the file: 0x{:x}-0x{:x} and 0x{:x}-0x{:x}",
+                         a.header.name(), b.header.name(), ra.start, ra.end, rb.start, rb.end);
+            }
+            OverlapKind::Address => {
+                let (ra, rb) = (a.address_range(), b.address_range());
+                println!("Sections {} and {} overlap in memory: 0x{:08x}-0x{:08x} and 0x{:08x}-0x{:08x}",
+                         a.header.name(), b.header.name(), ra.start, ra.end, rb.start, rb.end);
+            }
+        }
+    }
+
+    if overlaps.is_empty() && !quiet {
+        println!("No overlapping sections.");
+    }
+}
+
+///
+/// Check a file for --validate, printing any problems to stderr: its
+/// section headers must not overlap, and each text section must decode
+/// from start to end. Returns the exit status for the file.
+///
+fn validate(infile: &str, container: &FileContainer, opts: &Options) -> i32 {
+    let mut status = EXIT_OK;
+
+    for overlap in container.overlapping_sections() {
+        let a = container.sections[overlap.first].header.name();
+        let b = container.sections[overlap.second].header.name();
+        let kind = match overlap.kind {
+            OverlapKind::FileOffset => "in the file",
+            OverlapKind::Address => "in memory",
+        };
+
+        eprintln!("{}: sections {} and {} overlap {}", infile, a, b, kind);
+        status = EXIT_PARSE_ERROR;
+    }
+
+    let mut decoder = Decoder {
+        max_len: opts.max_insn_bytes,
+        ..Decoder::new()
+    };
+
+    for (sec_num, section) in container.code_sections() {
+        let data = match container.try_section_data(sec_num) {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        let vaddr = section.header.vaddr;
+        let mut cursor: Cursor<&[u8]> = Cursor::new(data);
+        let mut gaps = 0;
+        // The undecodable run being collected, as offsets into `data`.
+        let mut run: Option<(u64, u64)> = None;
+
+        let report = |(from, to): (u64, u64)| {
+            eprintln!("{}: {}: undecodable bytes at 0x{:08x}-0x{:08x}",
+                      infile, section.header.name(), vaddr.wrapping_add(from as u32),
+                      vaddr.wrapping_add(to as u32).wrapping_sub(1));
+        };
+
+        while (cursor.position() as usize) < data.len() {
+            let pos = cursor.position();
+
+            if decoder.decode_instruction(&mut cursor).is_ok() {
+                if let Some(run) = run.take() {
+                    report(run);
+                }
+                continue;
+            }
+
+            let next = resync_position(data, pos, vaddr, &decoder, opts.resync);
+            run = match run {
+                Some((from, _)) => Some((from, next)),
+                None => Some((pos, next)),
+            };
+            gaps += next - pos;
+            cursor.set_position(next);
+        }
+
+        if let Some(run) = run {
+            report(run);
+        }
+
+        if gaps > 0 {
+            eprintln!("{}: {}: {} undecodable byte{}", infile, section.header.name(),
+                      gaps, if gaps == 1 { "" } else { "s" });
+            status = status.max(EXIT_DECODE_GAPS);
+        }
+    }
+
+    status
+}
+
+///
+/// Print the --analyze report on each text section: how its control
+/// transfers and operands address memory, as a hint to whether the
+/// code is position-independent. The counts are:
+///
+///   - PC-relative branches: branches and BSBs, whose targets are
+///     displacements from the instruction
+///   - Absolute jumps and calls: JMP, JSB and CALL to an absolute or
+///     absolute deferred address
+///   - Other jumps and calls: those through a register or a
+///     displacement from one, whose targets aren't known statically
+///   - Absolute operands: operands of any instruction in the absolute
+///     and absolute deferred modes. Each ties the code to where it, or
+///     the data it uses, is loaded.
+///   - Outside all sections: absolute operands and branch targets that
+///     no section covers. In an object file these are usually
+///     references the link editor has yet to fill in; in an
+///     executable, fixed addresses such as I/O registers or ROM.
+///
+/// Code with no absolute operands is reported as position-independent.
+///
+fn analyze(container: &FileContainer, opts: &Options) {
+    if container.code_sections().next().is_none() {
+        println!("No code sections found.");
+        return;
+    }
+
+    let mut decoder = Decoder {
+        max_len: opts.max_insn_bytes,
+        ..Decoder::new()
+    };
+
+    for (sec_num, section) in container.code_sections() {
+        let data = match container.try_section_data(sec_num) {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        let vaddr = section.header.vaddr;
+        let outside = |addr: u32| container.section_containing(addr).is_none();
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(data);
+        let mut instructions = 0;
+        let mut undecodable = 0;
+        let mut relative = 0;
+        let mut absolute_transfers = 0;
+        let mut other_transfers = 0;
+        let mut absolute_operands = 0;
+        let mut outside_refs = 0;
+
+        while (cursor.position() as usize) < data.len() {
+            let pos = cursor.position();
+            let addr = vaddr.wrapping_add(pos as u32);
+
+            if decoder.decode_instruction(&mut cursor).is_err() {
+                let next = resync_position(data, pos, vaddr, &decoder, opts.resync);
+                undecodable += next - pos;
+                cursor.set_position(next);
+                continue;
+            }
+
+            let insn = &decoder.ir;
+            instructions += 1;
+
+            let operands = &insn.operands[..insn.operand_count as usize];
+            let is_absolute = |mode| matches!(mode, AddrMode::Absolute | AddrMode::AbsoluteDeferred);
+
+            if let Some(target) = insn.branch_target(addr) {
+                relative += 1;
+                if outside(target) {
+                    outside_refs += 1;
+                }
+            } else if matches!(insn.flow(), Flow::Branch | Flow::Call) {
+                if operands.iter().any(|op| is_absolute(op.mode())) {
+                    absolute_transfers += 1;
+                } else {
+                    other_transfers += 1;
+                }
+            }
+
+            for op in operands.iter().filter(|op| is_absolute(op.mode())) {
+                absolute_operands += 1;
+                if outside(op.embedded()) {
+                    outside_refs += 1;
+                }
+            }
+        }
+
+        println!("Section {}: {} instructions", section.header.name(), instructions);
+        if undecodable > 0 {
+            println!("    Undecodable bytes:         {}", undecodable);
+        }
+        println!("    PC-relative branches:      {}", relative);
+        println!("    Absolute jumps and calls:  {}", absolute_transfers);
+        println!("    Other jumps and calls:     {}", other_transfers);
+        println!("    Absolute operands:         {}", absolute_operands);
+        println!("    Outside all sections:      {}", outside_refs);
+
+        if absolute_operands == 0 {
+            println!("    Position-independent: no absolute addresses.");
+        } else {
+            println!("    Not position-independent: {} absolute address{}.",
+                     absolute_operands, if absolute_operands == 1 { "" } else { "es" });
+        }
+    }
+}
+
+///
+/// The symbols to annotate a listing with: those from the file's symbol
+/// table, if any, merged with those from --symbols-file.
+///
+fn symbol_map(container: Option<&FileContainer>, opts: &Options) -> SymbolMap {
+    let mut symbols = match container {
+        Some(container) => container.symbol_map(),
+        None => SymbolMap::new(),
+    };
+
+    if let Some(extra) = &opts.extra_symbols {
+        symbols.merge(extra, opts.symbols_override);
+    }
+
+    symbols
+}
+
+///
+/// Source files read for --source, by the name the symbol table gives
+/// them. A file that can't be read is remembered as `None`, so that it
+/// is only tried once.
+///
+struct SourceFiles<'a> {
+    dir: &'a Path,
+    files: HashMap<String, Option<Vec<String>>>,
+}
+
+impl<'a> SourceFiles<'a> {
+    fn new(dir: &'a Path) -> Self {
+        SourceFiles {
+            dir,
+            files: HashMap::new(),
         }
     }
+
+    ///
+    /// Line `line` (counting from 1) of the named file, looked for in
+    /// the source directory both by the name as given and by its last
+    /// component alone.
+    ///
+    fn line(&mut self, name: &str, line: u32) -> Option<&str> {
+        let dir = self.dir;
+
+        let lines = self.files.entry(name.to_owned()).or_insert_with(|| {
+            let path = Path::new(name);
+            let text = std::fs::read_to_string(dir.join(path)).or_else(|e| match path.file_name() {
+                Some(base) => std::fs::read_to_string(dir.join(base)),
+                None => Err(e),
+            });
+
+            text.ok().map(|text| text.lines().map(String::from).collect())
+        });
+
+        lines.as_ref()?.get((line as usize).checked_sub(1)?).map(|s| s.as_str())
+    }
+}
+
+///
+/// Disassemble `code.data` from offset `code.start` to the end.
+///
+/// Each line is written to stdout as soon as its instruction is
+/// decoded, so memory use doesn't grow with the size of the input. If
+/// the reader goes away (e.g. the output is piped to `head`), the
+/// listing stops quietly.
+///
+fn disassemble_data(code: &Code, symbols: &SymbolMap, container: Option<&FileContainer>, opts: &Options) {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    let result = write_listing(&mut out, code, symbols, container, opts)
+        .and_then(|_| out.flush());
+
+    match result {
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+        Err(e) => eprintln!("Couldn't write listing: {}", e),
+        Ok(()) => {}
+    }
+}
+
+fn write_listing(out: &mut dyn Write, code: &Code, symbols: &SymbolMap,
+                 container: Option<&FileContainer>, opts: &Options) -> io::Result<()> {
+    let Code { data, start, vaddr, file_offset } = *code;
+
+    // JSON output is one object per instruction, with nothing else
+    // mixed in.
+    let json = opts.output == OutputFormat::Json;
+
+    if json {
+        writeln!(out, "{}", render::json_header())?;
+    }
+
+    let mut decoder = Decoder {
+        max_len: opts.max_insn_bytes,
+        ..Decoder::new()
+    };
+    let mut cursor: Cursor<&[u8]> = Cursor::new(data);
+    cursor.set_position(start as u64);
+
+    // In blocks mode, every branch target and every
+    // instruction following the end of a basic block gets
+    // a label.
+    let targets = if opts.blocks {
+        flow::branch_targets(data, start, vaddr)
+    } else {
+        BTreeSet::new()
+    };
+
+    let block_label = |addr: u32| -> String {
+        match symbols.get(addr) {
+            Some(name) => name.to_owned(),
+            None => flow::label(addr),
+        }
+    };
+
+    // With --source, the source lines that start at each address.
+    let mut source_lines: BTreeMap<u32, Vec<SourceLine>> = BTreeMap::new();
+    let mut sources = opts.source_dir.as_deref().map(SourceFiles::new);
+
+    if let (Some(container), Some(_)) = (container, &sources) {
+        if opts.output == OutputFormat::Text {
+            for sec_num in 0..container.sections.len() {
+                for line in container.source_lines(sec_num) {
+                    source_lines.entry(line.addr).or_default().push(line);
+                }
+            }
+        }
+    }
+
+    let mut first = true;
+    let mut prev_ends_block = false;
+    let mut prev_terminator = false;
+    let mut total_cycles: u64 = 0;
+    let mut untimed = 0;
+
+    // A run of NOPs being folded: its first address and length.
+    let mut nops: Option<(u32, usize)> = None;
+    let fold_nops = opts.fold_nops && !json;
+    let indent = if opts.blocks && opts.output == OutputFormat::Text { "    " } else { "" };
+
+    let width = address_width(&opts.format, vaddr.wrapping_add(data.len().saturating_sub(1) as u32));
+
+    let print_nops = |out: &mut dyn Write, from: u32, to: u32, count: usize| {
+        writeln!(out, "{}{:0width$x}-{:0width$x}  ... ({} NOP{})", indent, from, to.wrapping_sub(1), count,
+                 if count == 1 { "" } else { "s" }, width = width)
+    };
+
+    // The file offset and address (or symbolic address) columns that
+    // start each line.
+    let print_address = |out: &mut dyn Write, addr: u32, offset: u64| -> io::Result<()> {
+        if opts.show_offsets && opts.output == OutputFormat::Text {
+            write!(out, "[{:08x}]  ", file_offset + offset)?;
+        }
+
+        if opts.symbolic && !json {
+            write!(out, "{:<24}", format!("{}:", symbols.relative(addr)))
+        } else if opts.format.verbosity >= 1 && !json {
+            write!(out, "{:0width$x}:  ", addr, width = width)
+        } else {
+            Ok(())
+        }
+    };
+
+    loop {
+        let pos = cursor.position();
+        let addr = vaddr.wrapping_add(pos as u32);
+
+        if decoder.decode_instruction(&mut cursor).is_err() {
+            if !opts.lossy || pos as usize >= data.len() {
+                break;
+            }
+
+            // Rewind to the start of the instruction and show bytes as
+            // data up to the point where decoding resumes.
+            let next = resync_position(data, pos, vaddr, &decoder, opts.resync);
+            cursor.set_position(next);
+
+            if let Some((from, count)) = nops {
+                print_nops(out, from, addr, count)?;
+                nops = None;
+            }
+
+            for offset in pos..next {
+                let addr = vaddr.wrapping_add(offset as u32);
+
+                write!(out, "{}", indent)?;
+                print_address(out, addr, offset)?;
+                if opts.timing && !json {
+                    write!(out, "{:>4}  ", "")?;
+                }
+
+                let ctx = RenderContext {
+                    addr,
+                    file_offset: file_offset + offset,
+                    symbols,
+                    format: &opts.format,
+                };
+
+                writeln!(out, "{}", opts.renderer.render_byte(data[offset as usize], &ctx))?;
+            }
+
+            continue;
+        }
+
+        // For working on the decoder: everything it produced, with no
+        // other formatting.
+        if opts.debug_insn {
+            writeln!(out, "{:0width$x}: {:#?}", addr, decoder.ir, width = width)?;
+            continue;
+        }
+
+        let starts_block = opts.blocks && !json &&
+            (first || prev_ends_block || targets.contains(&addr) || symbols.get(addr).is_some());
+
+        // A NOP run ends at the first other instruction, or at a block
+        // boundary so that no label is folded away.
+        let is_nop = fold_nops && decoder.ir.is_nop();
+
+        if let Some((from, count)) = nops {
+            if is_nop && !starts_block {
+                nops = Some((from, count + 1));
+                total_cycles += u64::from(timing::estimate(&decoder.ir).unwrap_or(0));
+                continue;
+            }
+
+            print_nops(out, from, addr, count)?;
+            nops = None;
+        }
+
+        if opts.blocks && !json {
+            if starts_block {
+                // Make fall-through from the previous block visible.
+                if !first && !prev_terminator {
+                    writeln!(out, "    ; falls through")?;
+                }
+                writeln!(out)?;
+                writeln!(out, "{}:", block_label(addr))?;
+            }
+
+            first = false;
+            prev_ends_block = decoder.ir.ends_block();
+            prev_terminator = decoder.ir.is_terminator();
+        }
+
+        if let (Some(lines), Some(sources)) = (source_lines.get(&addr), sources.as_mut()) {
+            for line in lines {
+                let name = line.file.as_deref().unwrap_or("?");
+
+                match sources.line(name, line.line) {
+                    Some(text) => writeln!(out, "{}; {}:{}  {}", indent, name, line.line, text.trim_end())?,
+                    None => writeln!(out, "{}; {}:{}", indent, name, line.line)?,
+                }
+            }
+        }
+
+        if is_nop {
+            nops = Some((addr, 1));
+            total_cycles += u64::from(timing::estimate(&decoder.ir).unwrap_or(0));
+            continue;
+        }
+
+        write!(out, "{}", indent)?;
+
+        // In symbolic mode, addresses are shown relative to
+        // the nearest symbol so that listings of two builds
+        // diff cleanly.
+        print_address(out, addr, pos)?;
+
+        // Estimated cycles are shown in their own column
+        // ahead of the byte dump.
+        if opts.timing && !json {
+            match timing::estimate(&decoder.ir) {
+                Some(cycles) => {
+                    total_cycles += u64::from(cycles);
+                    write!(out, "{:>4}  ", cycles)?;
+                }
+                None => {
+                    untimed += 1;
+                    write!(out, "{:>4}  ", "?")?;
+                }
+            }
+        }
+
+        let ctx = RenderContext {
+            addr,
+            file_offset: file_offset + pos,
+            symbols,
+            format: &opts.format,
+        };
+
+        write!(out, "{}", opts.renderer.render(&decoder.ir, &ctx))?;
+
+        if json {
+            writeln!(out)?;
+            continue;
+        }
+
+        if let Some(target) = decoder.ir.branch_target(addr) {
+            if opts.blocks && targets.contains(&target) {
+                write!(out, "  <{}>", block_label(target))?;
+            } else if opts.symbolic || opts.format.verbosity >= 1 {
+                write!(out, "  <{}>", symbols.relative(target))?;
+            }
+        }
+
+        if opts.string_refs {
+            if let Some(container) = container {
+                if let Some(note) = string_note(&decoder.ir, addr, container) {
+                    write!(out, "  ; {}", note)?;
+                }
+            }
+        }
+
+        writeln!(out)?;
+    }
+
+    if let Some((from, count)) = nops {
+        print_nops(out, from, vaddr.wrapping_add(cursor.position() as u32), count)?;
+    }
+
+    if opts.timing && !json {
+        writeln!(out)?;
+        writeln!(out, "Estimated cycles: {}", total_cycles)?;
+        if untimed > 0 {
+            writeln!(out, "Instructions without timing: {}", untimed)?;
+        }
+    }
+
+    Ok(())
+}
+
+///
+/// Print the basic blocks of `data` from `start` as a Graphviz DOT
+/// graph. Branches are solid, fall-through dotted, and calls dashed;
+/// targets outside the data (usually called functions) are drawn as
+/// ellipses.
+///
+fn print_cfg_dot(name: &str, data: &[u8], start: usize, vaddr: u32, symbols: &SymbolMap) {
+    let blocks = flow::basic_blocks(data, start, vaddr);
+    let starts: BTreeSet<u32> = blocks.iter().map(|b| b.start).collect();
+    let mut outside: BTreeSet<u32> = BTreeSet::new();
+
+    println!("digraph \"{}\" {{", name.replace('"', "\\\""));
+    println!("    node [shape=box, fontname=\"monospace\"];");
+
+    for block in &blocks {
+        println!("    \"{}\" [label=\"{}\\n{:08x}-{:08x}\"];",
+                 flow::label(block.start), symbols.relative(block.start),
+                 block.start, block.end.wrapping_sub(1));
+    }
+
+    for block in &blocks {
+        for edge in &block.edges {
+            let style = match edge.kind {
+                EdgeKind::FallThrough => "dotted",
+                EdgeKind::Branch => "solid",
+                EdgeKind::Call => "dashed",
+            };
+
+            if !starts.contains(&edge.to) {
+                outside.insert(edge.to);
+            }
+
+            println!("    \"{}\" -> \"{}\" [style={}];",
+                     flow::label(block.start), flow::label(edge.to), style);
+        }
+    }
+
+    for addr in outside {
+        println!("    \"{}\" [shape=ellipse, label=\"{}\"];", flow::label(addr), symbols.relative(addr));
+    }
+
+    println!("}}");
+}
+
+/// The longest string shown by --string-refs.
+const STRING_REF_LEN: usize = 40;
+
+///
+/// If one of the instruction's operands is the address of a string,
+/// return the string, quoted, for a note in the listing. Addresses come
+/// from absolute operands, word immediates, and displacements from %pc.
+///
+fn string_note(insn: &Instruction, addr: u32, container: &FileContainer) -> Option<String> {
+    let mut regs = RegisterFile::default();
+    regs.regs[15] = Some(addr);
+
+    insn.operands[..insn.operand_count as usize].iter()
+        .filter_map(|op| match op.mode() {
+            AddrMode::Absolute => Some(op.embedded()),
+            AddrMode::WordImmediate => Some(op.embedded()),
+            AddrMode::AbsoluteDeferred => None,
+            _ if op.register() == Some(15) => op.effective_address(Some(&regs)),
+            _ => None,
+        })
+        .find_map(|target| container.string_at(target, STRING_REF_LEN))
+        .map(|(text, cut)| format!("{:?}{}", text, if cut { "..." } else { "" }))
+}
+
+///
+/// The contents of the input file, either read into memory or, with the
+/// "mmap" feature, mapped from the file.
+///
+enum Input {
+    Read(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Input::Read(buf) => buf,
+            #[cfg(feature = "mmap")]
+            Input::Mapped(map) => map,
+        }
+    }
+}
+
+///
+/// Parse a decimal number, or a hexadecimal one with a "0x" prefix.
+///
+fn parse_number(s: &str) -> Option<usize> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        usize::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// Instructions decoded by --self-test, with the text each should
+/// produce. Between them they use every addressing mode.
+static SELF_TEST: &[(&[u8], &str)] = &[
+    (&[0x84, 0x05, 0x40], "MOVW &5,%r0"),
+    (&[0x84, 0xff, 0x40], "MOVW &-1,%r0"),
+    (&[0x84, 0x41, 0x40], "MOVW %r1,%r0"),
+    (&[0x84, 0x52, 0x40], "MOVW (%r2),%r0"),
+    (&[0x84, 0x64, 0x40], "MOVW 4(%fp),%r0"),
+    (&[0x84, 0x74, 0x40], "MOVW 4(%ap),%r0"),
+    (&[0x84, 0x4f, 0x78, 0x56, 0x34, 0x12, 0x40], "MOVW &0x12345678,%r0"),
+    (&[0x84, 0x5f, 0x34, 0x12, 0x40], "MOVW &0x1234,%r0"),
+    (&[0x84, 0x6f, 0x10, 0x40], "MOVW &16,%r0"),
+    (&[0x84, 0x7f, 0x00, 0x10, 0x00, 0x00, 0x40], "MOVW $0x1000,%r0"),
+    (&[0x84, 0xef, 0x00, 0x10, 0x00, 0x00, 0x40], "MOVW *$0x1000,%r0"),
+    (&[0x84, 0x83, 0x10, 0x00, 0x00, 0x00, 0x40], "MOVW 0x10(%r3),%r0"),
+    (&[0x84, 0x93, 0x10, 0x00, 0x00, 0x00, 0x40], "MOVW *0x10(%r3),%r0"),
+    (&[0x84, 0xa3, 0x10, 0x00, 0x40], "MOVW 0x10(%r3),%r0"),
+    (&[0x84, 0xb3, 0x10, 0x00, 0x40], "MOVW *0x10(%r3),%r0"),
+    (&[0x84, 0xc9, 0xfc, 0x40], "MOVW -4(%fp),%r0"),
+    (&[0x84, 0xd9, 0x04, 0x40], "MOVW *4(%fp),%r0"),
+    (&[0x87, 0xe7, 0xff, 0x40], "MOVB {sbyte}&-1,%r0"),
+    (&[0x7b, 0x10], "BRB 16"),
+    (&[0x7a, 0x00, 0x01], "BRH 256"),
+    (&[0x70], "NOP"),
+    (&[0x30, 0x45], "RETG"),
+];
+
+///
+/// The index of the first operand whose recorded offset is not where it
+/// follows the opcode and the operands before it, if any.
+///
+fn misplaced_operand(ir: &Instruction) -> Option<usize> {
+    let mut offset = ir.opcode_len();
+
+    for (i, op) in ir.operands[..ir.operand_count as usize].iter().enumerate() {
+        if op.offset() != offset {
+            return Some(i);
+        }
+        offset += op.byte_size() as usize;
+    }
+
+    None
+}
+
+///
+/// Decode the built-in corpus and check each instruction's length,
+/// text, re-encoding and opcode length, then check the decode tables
+/// themselves. Returns true if everything passes.
+///
+fn self_test() -> bool {
+    let mut failures = 0;
+
+    for (bytes, expected) in SELF_TEST {
+        let result = match decode::decode_one(bytes) {
+            Ok(ref ir) if ir.encode().len() != bytes.len() => {
+                Err(format!("decoded {} of {} bytes", ir.encode().len(), bytes.len()))
+            }
+            Ok(ref ir) => {
+                let operands: Vec<String> = ir.operands[..ir.operand_count as usize]
+                    .iter()
+                    .map(|op| op.to_string())
+                    .collect();
+
+                let text = if operands.is_empty() {
+                    ir.name.to_string()
+                } else {
+                    format!("{} {}", ir.name, operands.join(","))
+                };
+
+                if text != *expected {
+                    Err(format!("got \"{}\"", text))
+                } else if ir.encode() != *bytes {
+                    Err("re-encoding differs".to_string())
+                } else if ir.opcode_len() != if bytes[0] == 0x30 { 2 } else { 1 } {
+                    Err(format!("opcode length {}", ir.opcode_len()))
+                } else if let Some(i) = misplaced_operand(ir) {
+                    Err(format!("operand {} at offset {}", i, ir.operands[i].offset()))
+                } else {
+                    Ok(())
+                }
+            }
+            Err(e) => Err(e.to_string()),
+        };
+
+        if let Err(why) = result {
+            println!("FAIL {:02x?} \"{}\": {}", bytes, expected, why);
+            failures += 1;
+        }
+    }
+
+    println!("self-test: {} of {} passed", SELF_TEST.len() - failures, SELF_TEST.len());
+
+    if let Some(name) = decode::find_unsized_literal() {
+        println!("FAIL decode table: {} has a literal operand but no literal size", name);
+        return false;
+    }
+
+    failures == 0
+}
+
+///
+/// The offset at which to resume decoding after a failure at `pos`,
+/// following the given strategy. This is always past `pos`, and at most
+/// the end of the data.
+///
+fn resync_position(data: &[u8], pos: u64, vaddr: u32, decoder: &Decoder, resync: Resync) -> u64 {
+    let end = data.len() as u64;
+
+    // Alignment is of the address, not the offset into the section.
+    let align = |size: u64| -> u64 {
+        let addr = u64::from(vaddr) + pos + 1;
+        let aligned = (addr + size - 1) & !(size - 1);
+        (aligned - u64::from(vaddr)).min(end)
+    };
+
+    match resync {
+        Resync::Byte => pos + 1,
+        Resync::Halfword => align(2),
+        Resync::Word => align(4),
+        Resync::Decodable => {
+            let mut trial = Decoder {
+                max_len: decoder.max_len,
+                ..Decoder::new()
+            };
+
+            (pos + 1..end).find(|&next| {
+                let mut cursor = Cursor::new(data);
+                cursor.set_position(next);
+
+                trial.decode_instruction(&mut cursor).is_ok() &&
+                    (cursor.position() >= end || trial.decode_instruction(&mut cursor).is_ok())
+            }).unwrap_or(end)
+        }
+    }
+}
+
+///
+/// Print every slot of the decode tables with its mnemonic, data type
+/// and operand types. Unassigned slots, entries whose opcode doesn't
+/// match their slot, and names used more than once are marked.
+///
+fn print_opcode_table() {
+    let mut seen: BTreeSet<&str> = BTreeSet::new();
+
+    println!("{:<8}{:<10}{:<7}Operands", "Opcode", "Mnemonic", "Type");
+
+    for (slot, entry) in decode::opcode_table() {
+        let entry = match entry {
+            Some(entry) => entry,
+            None if slot == 0x30 => {
+                println!("0x{:02x}    (prefix for the halfword opcodes)", slot);
+                continue;
+            }
+            None => {
+                println!("0x{:02x}    -", slot);
+                continue;
+            }
+        };
+
+        let operands: Vec<String> = entry.operands.iter()
+            .take_while(|op| **op != OpType::None)
+            .map(|op| format!("{:?}", op))
+            .collect();
+
+        let mut line = format!("{:<8}{:<10}{:<7}{:<16}", format!("0x{:02x}", slot), entry.name,
+                               format!("{:?}", entry.data_type),
+                               if operands.is_empty() { String::from("-") } else { operands.join(",") });
+
+        if entry.opcode != slot {
+            line.push_str(&format!("  ; entry says 0x{:02x}", entry.opcode));
+        }
+
+        if !seen.insert(entry.name) {
+            line.push_str("  ; duplicate name");
+        }
+
+        println!("{}", line.trim_end());
+    }
+}
+
+///
+/// Print how much of the WE32100 instruction set the decoder covers,
+/// by comparing its tables with `decode::REFERENCE_OPCODES`: the
+/// defined opcodes it decodes, those it doesn't, and those it decodes
+/// under another name or that aren't defined at all. The rest of the
+/// byte opcodes are unassigned, and are listed as such rather than as
+/// gaps. Returns true if the decoder matches the reference exactly.
+///
+fn print_coverage() -> bool {
+    let reference: BTreeMap<u16, &str> = decode::REFERENCE_OPCODES.iter().cloned().collect();
+    let decoded: BTreeMap<u16, &str> = decode::opcode_table()
+        .filter_map(|(slot, entry)| entry.map(|entry| (slot, entry.name)))
+        .collect();
+
+    let covered = reference.iter()
+        .filter(|(opcode, name)| decoded.get(opcode) == Some(name))
+        .count();
+    let missing: Vec<String> = reference.iter()
+        .filter(|(opcode, _)| !decoded.contains_key(opcode))
+        .map(|(opcode, name)| format!("0x{:02x} {}", opcode, name))
+        .collect();
+    let wrong: Vec<String> = decoded.iter()
+        .filter(|(opcode, name)| matches!(reference.get(opcode), Some(r) if r != *name))
+        .map(|(opcode, name)| format!("0x{:02x} {} (defined as {})", opcode, name, reference[opcode]))
+        .collect();
+    let extra: Vec<String> = decoded.iter()
+        .filter(|(opcode, _)| !reference.contains_key(opcode))
+        .map(|(opcode, name)| format!("0x{:02x} {}", opcode, name))
+        .collect();
+    let unassigned: Vec<String> = (0..0x100u16)
+        .filter(|slot| *slot != 0x30 && !reference.contains_key(slot) && !decoded.contains_key(slot))
+        .map(|slot| format!("0x{:02x}", slot))
+        .collect();
+
+    let list = |items: &[String]| if items.is_empty() { String::from("none") } else { items.join(", ") };
+
+    println!("Defined opcodes decoded: {} of {} ({:.1}%)", covered, reference.len(),
+             100.0 * covered as f64 / reference.len() as f64);
+    println!("Defined but not decoded: {}", list(&missing));
+    println!("Decoded under another name: {}", list(&wrong));
+    println!("Decoded but not defined: {}", list(&extra));
+    println!("Unassigned byte opcodes ({}): {}", unassigned.len(), list(&unassigned));
+
+    missing.is_empty() && wrong.is_empty() && extra.is_empty()
+}
+
+///
+/// Print each field of a processor status word.
+///
+fn decode_psw(value: u32) {
+    println!("PSW 0x{:08x}", value);
+
+    for field in PSW_FIELDS {
+        let v = field.value(value);
+        let bits = if field.width == 1 {
+            format!("{}", field.shift)
+        } else {
+            format!("{}-{}", field.shift, field.shift + field.width - 1)
+        };
+
+        print!("  {:<4}{:>6}  {:>2}  {}", field.name, bits, v, field.description);
+
+        if field.name == "PM" || field.name == "CM" {
+            print!(" ({})", execution_level_name(v));
+        }
+
+        println!();
+    }
 }
 
 fn main() {
-    let matches = App::new("WE32100 Disassembler")
-        .version("1.0")
+    let app = App::new("WE32100 Disassembler")
+        .version(we32dis::VERSION)
         .author("Seth J. Morabito <web@loomcom.com>")
         .about("WE32100 Disassembler")
         .arg(Arg::with_name("offset")
@@ -69,28 +1295,558 @@ fn main() {
              .long("offset")
              .help("Offset within the file to start disassembly")
              .takes_value(true))
+        .arg(Arg::with_name("entry_trace")
+             .long("entry-trace")
+             .help("Disassemble from the entry point in the optional header"))
+        .arg(Arg::with_name("function")
+             .value_name("NAME")
+             .long("function")
+             .help("Disassemble only the named function")
+             .conflicts_with("entry_trace")
+             .takes_value(true))
+        .arg(Arg::with_name("time_format")
+             .value_name("FORMAT")
+             .long("time-format")
+             .help("Format for the file header timestamp")
+             .possible_values(&["rfc2822", "iso8601"])
+             .default_value("rfc2822")
+             .takes_value(true))
+        .arg(Arg::with_name("pad_width")
+             .value_name("WIDTH")
+             .long("pad-width")
+             .help("Column at which mnemonics start in the listing")
+             .takes_value(true))
+        .arg(Arg::with_name("case")
+             .value_name("CASE")
+             .long("case")
+             .help("Letter case of mnemonics in the listing")
+             .possible_values(&["upper", "lower"])
+             .default_value("upper")
+             .takes_value(true))
+        .arg(Arg::with_name("reg_style")
+             .value_name("STYLE")
+             .long("reg-style")
+             .help("How to name registers: %fp, %ap and so on (special), %r0 to %r15 \
+                    (numeric), or special names without the % (bare)")
+             .possible_values(&["special", "numeric", "bare"])
+             .default_value("special")
+             .takes_value(true))
+        .arg(Arg::with_name("address_width")
+             .value_name("DIGITS")
+             .long("address-width")
+             .help("Minimum hex digits in instruction addresses, from 1 to 8, or \"auto\" for \
+                    just enough for the listing (default 8)")
+             .takes_value(true))
+        .arg(Arg::with_name("group_bytes")
+             .long("group-bytes")
+             .help("Separate the opcode and each operand in the byte dump, as in \"84 | 40 | 41\""))
+        .arg(Arg::with_name("symbolic")
+             .long("symbolic")
+             .help("Show addresses and branch targets relative to symbols"))
+        .arg(Arg::with_name("symbols_file")
+             .value_name("FILE")
+             .long("symbols-file")
+             .help("Also use the symbols in FILE, one \"ADDRESS NAME\" per line with the \
+                    address in hex")
+             .takes_value(true))
+        .arg(Arg::with_name("symbols_override")
+             .long("symbols-override")
+             .help("Prefer names from --symbols-file over the file's own symbols at the \
+                    same address")
+             .requires("symbols_file"))
+        .arg(Arg::with_name("strings")
+             .long("strings")
+             .help("Print the strings table instead of disassembling"))
+        .arg(Arg::with_name("strings_format")
+             .value_name("FORMAT")
+             .long("strings-format")
+             .help("Format for --strings")
+             .possible_values(&["annotated", "plain", "tsv"])
+             .default_value("annotated")
+             .takes_value(true))
+        .arg(Arg::with_name("ascii_strings")
+             .value_name("SECTION")
+             .long("ascii-strings")
+             .help("Print runs of printable ASCII in the named section, or in every \
+                    section, instead of disassembling")
+             .min_values(0)
+             .require_equals(true)
+             .takes_value(true))
+        .arg(Arg::with_name("min_len")
+             .value_name("LENGTH")
+             .long("min-len")
+             .help("Shortest run printed by --ascii-strings (default 4)")
+             .takes_value(true))
+        .arg(Arg::with_name("string_refs")
+             .long("string-refs")
+             .help("Show the string at the address an operand refers to, if there is one"))
+        .arg(Arg::with_name("show_offsets")
+             .long("show-offsets")
+             .help("Start each line with the instruction's offset in the input file, \
+                    as opposed to its load address"))
+        .arg(Arg::with_name("interactive")
+             .long("interactive")
+             .help("Read addresses and symbol names from stdin and disassemble at each"))
+        .arg(Arg::with_name("source")
+             .value_name("DIR")
+             .long("source")
+             .help("Show the source lines from the line number table above their code, \
+                    reading the source files from DIR")
+             .takes_value(true))
+        .arg(Arg::with_name("cfg_dot")
+             .long("cfg-dot")
+             .requires("function")
+             .help("Print the control-flow graph of the --function as Graphviz DOT"))
+        .arg(Arg::with_name("blocks")
+             .long("blocks")
+             .help("Group the listing into labeled basic blocks"))
+        .arg(Arg::with_name("timing")
+             .long("timing")
+             .help("Show approximate cycle counts and a total"))
+        .arg(Arg::with_name("fold_nops")
+             .long("fold-nops")
+             .help("Collapse runs of NOP padding into a single line"))
+        .arg(Arg::with_name("lossy")
+             .long("lossy")
+             .help("Show the first byte of an undecodable instruction as data and carry on, \
+                    instead of stopping"))
+        .arg(Arg::with_name("resync")
+             .value_name("STRATEGY")
+             .long("resync")
+             .help("Where to resume after an undecodable byte in lossy mode: the next byte, \
+                    the next halfword or word boundary, or the next byte from which two \
+                    instructions decode (default byte)")
+             .possible_values(&["byte", "half", "word", "decodable"])
+             .requires("lossy")
+             .takes_value(true))
+        .arg(Arg::with_name("max_insn_bytes")
+             .value_name("BYTES")
+             .long("max-insn-bytes")
+             .help("Treat an instruction longer than this as undecodable (default 25)")
+             .takes_value(true))
+        .arg(Arg::with_name("force")
+             .long("force")
+             .help("Disassemble the chosen section even if it is not marked as code"))
+        .arg(Arg::with_name("debug_insn")
+             .long("debug-insn")
+             .help("Print each decoded instruction's internal fields, for debugging the decoder"))
+        .arg(Arg::with_name("verbose")
+             .short("v")
+             .long("verbose")
+             .multiple(true)
+             .help("Add detail; repeat for more: addresses and branch targets, \
+                    flag effects, operand descriptors"))
+        .arg(Arg::with_name("output")
+             .value_name("FORMAT")
+             .long("output")
+             .help("Listing format")
+             .possible_values(&["text", "gas", "json"])
+             .default_value("text")
+             .takes_value(true))
+        .arg(Arg::with_name("quiet")
+             .short("q")
+             .long("quiet")
+             .help("Print only the listing, without headers or banners"))
+        .arg(Arg::with_name("check")
+             .long("check")
+             .help("Check the section headers for overlaps instead of disassembling"))
+        .arg(Arg::with_name("validate")
+             .long("validate")
+             .help("Check that the file parses and its text sections decode, reporting \
+                    problems on stderr instead of disassembling. Exits 0 if the file is \
+                    clean, 1 if it doesn't parse or its sections overlap, and 2 if a text \
+                    section has undecodable bytes"))
+        .arg(Arg::with_name("map")
+             .long("map")
+             .help("Print an address-sorted map of defined symbols instead of disassembling"))
+        .arg(Arg::with_name("analyze")
+             .long("analyze")
+             .help("Report how each text section addresses memory, as a hint to whether \
+                    it is position-independent, instead of disassembling"))
+        .arg(Arg::with_name("symbols_json")
+             .long("symbols-json")
+             .help("Print the symbol table as JSON, one symbol per line, instead of disassembling"))
+        .arg(Arg::with_name("summary")
+             .long("summary")
+             .help("Print a one-line summary of each file instead of disassembling; \
+                    a file that isn't COFF gets a line saying so, and exit status 1"))
+        .arg(Arg::with_name("identify")
+             .long("identify")
+             .help("Print each file's length and raw file header, and with the hash \
+                    feature its SHA-256 digest, instead of disassembling; as with \
+                    --summary, a file that isn't COFF gets a line saying so"))
+        .arg(Arg::with_name("list_sections")
+             .long("list-sections")
+             .help("List the sections and their flags instead of disassembling"))
+        .arg(Arg::with_name("member")
+             .value_name("NAME")
+             .long("member")
+             .help("Disassemble the named member of an archive")
+             .takes_value(true))
+        .arg(Arg::with_name("list_members")
+             .long("list-members")
+             .help("List the members of an archive")
+             .conflicts_with("member"))
+        .arg(Arg::with_name("file_offset")
+             .value_name("OFFSET")
+             .long("file-offset")
+             .help("Offset of the COFF file within the input, for embedded objects")
+             .takes_value(true))
+        .arg(Arg::with_name("lenient")
+             .long("lenient")
+             .help("Read what is there of sections, symbols and strings cut short by a \
+                    truncated file, with a warning, instead of giving up"))
+        .arg(Arg::with_name("name_encoding")
+             .value_name("ENCODING")
+             .long("name-encoding")
+             .help("How to read the bytes of symbol and file names: as UTF-8, escaping bytes \
+                    that aren't valid (utf8), as Latin-1 (latin1), or as ASCII, escaping all \
+                    other bytes (raw)")
+             .possible_values(&["utf8", "latin1", "raw"])
+             .default_value("utf8")
+             .takes_value(true))
+        .arg(Arg::with_name("force_raw")
+             .long("force-raw")
+             .help("If the file can't be parsed as COFF, disassemble it as raw instructions"))
+        .arg(Arg::with_name("self_test")
+             .long("self-test")
+             .hidden(true)
+             .help("Check the decoder against a built-in corpus and exit"))
+        .arg(Arg::with_name("decode_psw")
+             .value_name("VALUE")
+             .long("decode-psw")
+             .help("Print the fields of a processor status word value and exit")
+             .takes_value(true))
+        .arg(Arg::with_name("opcode_table")
+             .long("opcode-table")
+             .help("Print the decoder's opcode table and exit"))
+        .arg(Arg::with_name("coverage")
+             .long("coverage")
+             .help("Compare the decoder's opcode table with the opcodes the WE32100 defines and exit"))
         .arg(Arg::with_name("INPUT")
              .value_name("FILE")
-             .help("Input file to decompile")
-             .required(true)
-             .index(1))
-        .get_matches();
+             .help("Input files to decompile")
+             .required_unless_one(&["self_test", "decode_psw", "opcode_table", "coverage"])
+             .multiple(true)
+             .index(1));
+
+    #[cfg(feature = "mmap")]
+    let app = app.arg(Arg::with_name("mmap")
+                      .long("mmap")
+                      .help("Memory-map the input file instead of reading it into memory"));
+
+    let matches = app.get_matches();
+
+    if matches.is_present("self_test") {
+        process::exit(if self_test() { 0 } else { 1 });
+    }
+
+    if matches.is_present("opcode_table") {
+        print_opcode_table();
+        return;
+    }
+
+    if matches.is_present("coverage") {
+        process::exit(if print_coverage() { 0 } else { 1 });
+    }
+
+    if let Some(value) = matches.value_of("decode_psw") {
+        match parse_number(value) {
+            Some(psw) if psw <= u32::MAX as usize => decode_psw(psw as u32),
+            _ => fail(format!("invalid PSW value: {}", value)),
+        }
+        return;
+    }
+
+    let mut format = FormatOptions {
+        verbosity: matches.occurrences_of("verbose").min(3) as u8,
+        case: match matches.value_of("case") {
+            Some("lower") => Case::Lower,
+            _ => Case::Upper,
+        },
+        reg_style: match matches.value_of("reg_style") {
+            Some("numeric") => RegStyle::Numeric,
+            Some("bare") => RegStyle::Bare,
+            _ => RegStyle::Special,
+        },
+        group_bytes: matches.is_present("group_bytes"),
+        ..FormatOptions::default()
+    };
+
+    if let Some(width) = matches.value_of("address_width") {
+        format.address_width = match width {
+            "auto" => 0,
+            _ => match width.parse() {
+                Ok(width) if (1..=8).contains(&width) => width,
+                _ => fail(format!("invalid address width: {}", width)),
+            },
+        };
+    }
+
+    if let Some(width) = matches.value_of("pad_width") {
+        match width.parse() {
+            Ok(w) => format.pad_width = w,
+            Err(_) => fail(format!("invalid pad width: {}", width)),
+        }
+    }
+
+    let max_insn_bytes = match matches.value_of("max_insn_bytes").map(str::parse) {
+        Some(Ok(len)) if len > 0 => len,
+        Some(_) => fail(format!("invalid instruction length: {}",
+                                matches.value_of("max_insn_bytes").unwrap())),
+        None => decode::MAX_INSTRUCTION_LEN,
+    };
+
+    let min_len = match matches.value_of("min_len").map(str::parse) {
+        Some(Ok(len)) if len > 0 => len,
+        Some(_) => fail(format!("invalid minimum length: {}", matches.value_of("min_len").unwrap())),
+        None => 4,
+    };
+
+    let strings = if matches.is_present("strings") {
+        match matches.value_of("strings_format") {
+            Some("plain") => Some(StringsFormat::Plain),
+            Some("tsv") => Some(StringsFormat::Tsv),
+            _ => Some(StringsFormat::Annotated),
+        }
+    } else {
+        None
+    };
+
+    let (output, renderer): (OutputFormat, Box<dyn InstructionRenderer>) =
+        match matches.value_of("output") {
+            Some("gas") => (OutputFormat::Gas, Box::new(GasRenderer)),
+            Some("json") => (OutputFormat::Json, Box::new(JsonRenderer)),
+            _ => (OutputFormat::Text, Box::new(TextRenderer)),
+        };
 
-    let infile = matches.value_of("INPUT").unwrap();
+    let extra_symbols = matches.value_of("symbols_file").map(|path| {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(why) => fail(format!("couldn't read {}: {}", path, why)),
+        };
 
+        match SymbolMap::parse(&text) {
+            Ok(symbols) => symbols,
+            Err(line) => fail(format!("{}: line {}: expected an address and a name", path, line)),
+        }
+    });
+
+    let opts = Options {
+        quiet: matches.is_present("quiet"),
+        check: matches.is_present("check"),
+        validate: matches.is_present("validate"),
+        list_sections: matches.is_present("list_sections"),
+        map: matches.is_present("map"),
+        analyze: matches.is_present("analyze"),
+        symbols_json: matches.is_present("symbols_json"),
+        summary: matches.is_present("summary"),
+        identify: matches.is_present("identify"),
+        strings,
+        ascii_strings: if matches.is_present("ascii_strings") {
+            Some(matches.value_of("ascii_strings").map(String::from))
+        } else {
+            None
+        },
+        min_len,
+        entry_trace: matches.is_present("entry_trace"),
+        function: matches.value_of("function").map(String::from),
+        symbolic: matches.is_present("symbolic"),
+        blocks: matches.is_present("blocks"),
+        timing: matches.is_present("timing"),
+        fold_nops: matches.is_present("fold_nops"),
+        lossy: matches.is_present("lossy"),
+        resync: match matches.value_of("resync") {
+            Some("half") => Resync::Halfword,
+            Some("word") => Resync::Word,
+            Some("decodable") => Resync::Decodable,
+            _ => Resync::Byte,
+        },
+        force: matches.is_present("force"),
+        debug_insn: matches.is_present("debug_insn"),
+        string_refs: matches.is_present("string_refs"),
+        show_offsets: matches.is_present("show_offsets"),
+        interactive: matches.is_present("interactive"),
+        source_dir: matches.value_of("source").map(PathBuf::from),
+        cfg_dot: matches.is_present("cfg_dot"),
+        max_insn_bytes,
+        extra_symbols,
+        symbols_override: matches.is_present("symbols_override"),
+        time_format: match matches.value_of("time_format") {
+            Some("iso8601") => TimeFormat::Iso8601,
+            _ => TimeFormat::Rfc2822,
+        },
+        format,
+        output,
+        renderer,
+    };
+
+    let base = match matches.value_of("file_offset") {
+        Some(offset) => match parse_number(offset) {
+            Some(base) => base,
+            None => fail(format!("invalid file offset: {}", offset)),
+        },
+        None => 0,
+    };
+
+    let inputs: Vec<&str> = matches.values_of("INPUT").unwrap().collect();
+    let banners = inputs.len() > 1 && !opts.quiet && !opts.summary && !opts.identify && !opts.validate;
+
+    let mut status = EXIT_OK;
+
+    for (i, infile) in inputs.iter().enumerate() {
+        if banners {
+            if i > 0 {
+                println!();
+            }
+            println!("==> {} <==", infile);
+        }
+
+        match disassemble_file(infile, base, &matches, &opts) {
+            Ok(file_status) => status = status.max(file_status),
+            Err(why) => {
+                eprintln!("error: {}", why);
+                status = status.max(EXIT_PARSE_ERROR);
+            }
+        }
+    }
+
+    if status != EXIT_OK {
+        process::exit(status);
+    }
+}
+
+///
+/// Print an error message to stderr and exit with a failure status.
+///
+fn fail(message: String) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+/// Disassemble one input file. If the file can't be read, the error is
+/// returned so that the caller can report it and carry on with the
+/// remaining inputs.
+fn disassemble_file(infile: &str, base: usize, matches: &ArgMatches, opts: &Options) -> Result<i32, String> {
     let path = Path::new(infile);
     let display = path.display();
 
-    let mut file = match File::open(&path) {
-        Err(why) => panic!("Couldn't open {}: {}", display, why.description()),
+    let mut file = match File::open(path) {
+        Err(why) => return Err(format!("couldn't open {}: {}", display, why)),
         Ok(file) => file,
     };
 
-    let mut buf = Vec::new();
+    #[cfg(feature = "mmap")]
+    let mapped = if matches.is_present("mmap") {
+        // The map is only valid while nothing else modifies the file,
+        // which is the usual state of affairs for an input file.
+        match unsafe { memmap2::Mmap::map(&file) } {
+            Ok(map) => Some(Input::Mapped(map)),
+            Err(why) => return Err(format!("couldn't map {}: {}", display, why)),
+        }
+    } else {
+        None
+    };
+
+    #[cfg(not(feature = "mmap"))]
+    let mapped = None;
+
+    let buf = match mapped {
+        Some(input) => input,
+        None => {
+            let mut buf = Vec::new();
 
-    if let Err(why) = file.read_to_end(&mut buf) {
-        panic!("Couldn't open {}: {}", display, why.description())
+            if let Err(why) = file.read_to_end(&mut buf) {
+                return Err(format!("couldn't read {}: {}", display, why));
+            }
+
+            Input::Read(buf)
+        }
+    };
+
+    // Where the object starts in the file; non-zero for an archive
+    // member.
+    let (object, origin) = if archive::is_archive(&buf) {
+        // An archive holds any number of COFF files; pick out the one
+        // the user asked for.
+        let archive = match Archive::read(&buf) {
+            Ok(archive) => archive,
+            Err(e) => {
+                eprintln!("{}: could not parse archive: {}", infile, e);
+                return Ok(EXIT_PARSE_ERROR);
+            }
+        };
+
+        if matches.is_present("list_members") {
+            archive.list_members();
+            return Ok(EXIT_OK);
+        }
+
+        let name = match matches.value_of("member") {
+            Some(name) => name,
+            None => {
+                eprintln!("{} is an archive; use --member or --list-members.", display);
+                return Ok(EXIT_NOT_FOUND);
+            }
+        };
+
+        match archive.member(name) {
+            Some(member) => (member.data, member.offset as u64),
+            None => {
+                eprintln!("No member named {} in {}.", name, display);
+                return Ok(EXIT_NOT_FOUND);
+            }
+        }
+    } else {
+        (&buf[..], 0)
+    };
+
+    let read_opts = ReadOptions {
+        lenient: matches.is_present("lenient"),
+        name_encoding: match matches.value_of("name_encoding") {
+            Some("latin1") => NameEncoding::Latin1,
+            Some("raw") => NameEncoding::Raw,
+            _ => NameEncoding::Utf8,
+        },
+    };
+
+    let parsed = match object.get(base..) {
+        Some(object) => FileContainer::read_with(object, &read_opts),
+        None => Err(CoffError::BadFileHeader),
+    };
+
+    if let Ok(container) = &parsed {
+        for warning in &container.warnings {
+            eprintln!("warning: {}: {}", infile, warning);
+        }
+    }
+
+    match parsed {
+        Ok(container) if opts.validate => return Ok(validate(infile, &container, opts)),
+        Ok(container) if opts.summary => println!("{}: {}", infile, container.summary()),
+        Ok(container) if opts.identify => println!("{}: {}", infile, container.identity()),
+        Ok(container) => return Ok(disassemble(&container, origin + base as u64, opts)),
+        // Summaries are one line per file on stdout, so a file that
+        // doesn't parse keeps its line there, as well as failing.
+        Err(e) if (opts.summary || opts.identify) && !opts.validate => {
+            println!("{}: not a COFF file ({})", infile, e);
+            return Ok(EXIT_PARSE_ERROR);
+        }
+        Err(e) if matches.is_present("force_raw") && !opts.validate => {
+            eprintln!("{}: could not parse file: {}; disassembling as raw data.", infile, e);
+            let code = Code {
+                data: object,
+                start: base,
+                vaddr: 0,
+                file_offset: origin,
+            };
+            disassemble_data(&code, &symbol_map(None, opts), None, opts);
+        }
+        Err(e) => {
+            eprintln!("{}: could not parse file: {}", infile, e);
+            return Ok(EXIT_PARSE_ERROR);
+        }
     }
 
-    disassemble(&buf);
+    Ok(EXIT_OK)
 }
@@ -0,0 +1,75 @@
+//!
+//! The WE32100 processor status word
+//!
+//! The PSW (register 11, "%psw") packs the condition flags together
+//! with the processor's execution level, interrupt priority level and
+//! trap and cache controls. Operating system code reads and writes it
+//! directly, for example in context-switch and interrupt routines.
+//!
+
+use core::fmt;
+
+/// A PSW field: its abbreviation, lowest bit, width in bits, and
+/// description.
+pub struct PswField {
+    pub name: &'static str,
+    pub shift: u32,
+    pub width: u32,
+    pub description: &'static str,
+}
+
+/// The fields of the PSW, lowest bit first. Bits 26 to 31 are unused.
+pub static PSW_FIELDS: &[PswField] = &[
+    PswField { name: "ET", shift: 0, width: 2, description: "exception type" },
+    PswField { name: "TM", shift: 2, width: 1, description: "trace mask" },
+    PswField { name: "ISC", shift: 3, width: 4, description: "internal state code" },
+    PswField { name: "I", shift: 7, width: 1, description: "initial PSW" },
+    PswField { name: "R", shift: 8, width: 1, description: "register save" },
+    PswField { name: "PM", shift: 9, width: 2, description: "previous execution level" },
+    PswField { name: "CM", shift: 11, width: 2, description: "current execution level" },
+    PswField { name: "IPL", shift: 13, width: 4, description: "interrupt priority level" },
+    PswField { name: "TE", shift: 17, width: 1, description: "trace enable" },
+    PswField { name: "C", shift: 18, width: 1, description: "carry" },
+    PswField { name: "V", shift: 19, width: 1, description: "overflow" },
+    PswField { name: "Z", shift: 20, width: 1, description: "zero" },
+    PswField { name: "N", shift: 21, width: 1, description: "negative" },
+    PswField { name: "OE", shift: 22, width: 1, description: "enable overflow trap" },
+    PswField { name: "CD", shift: 23, width: 1, description: "cache disable" },
+    PswField { name: "QIE", shift: 24, width: 1, description: "quick interrupt enable" },
+    PswField { name: "CFD", shift: 25, width: 1, description: "cache flush disable" },
+];
+
+impl PswField {
+    /// Extract this field from a PSW value.
+    pub fn value(&self, psw: u32) -> u32 {
+        (psw >> self.shift) & ((1 << self.width) - 1)
+    }
+}
+
+/// The name of an execution level, as held in the PM and CM fields.
+pub fn execution_level_name(level: u32) -> &'static str {
+    match level {
+        0 => "kernel",
+        1 => "executive",
+        2 => "supervisor",
+        _ => "user",
+    }
+}
+
+/// A PSW value, which displays as its fields, e.g.
+/// "ET=3 TM=0 ISC=0 ... CFD=0".
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Psw(pub u32);
+
+impl fmt::Display for Psw {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, field) in PSW_FIELDS.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={}", field.name, field.value(self.0))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+//!
+//! Pluggable instruction renderers
+//!
+//! The decoder produces `Instruction` values; a renderer turns each
+//! one into a line of output. Library users can implement
+//! `InstructionRenderer` to produce their own format.
+//!
+
+use std::fmt::Write;
+
+use crate::decode::{register_name, AddrMode, Data, FormatOptions, Instruction, Operand};
+use crate::symbols::SymbolMap;
+
+/// Everything a renderer knows about an instruction beyond the
+/// instruction itself.
+pub struct RenderContext<'a> {
+    /// Virtual address of the instruction
+    pub addr: u32,
+    /// Offset of the instruction in the input file. This differs from
+    /// the address, which is where the instruction is loaded: for a
+    /// section of a COFF file, it is the section's file pointer plus
+    /// the instruction's offset within the section.
+    pub file_offset: u64,
+    /// Symbols for the file being disassembled
+    pub symbols: &'a SymbolMap,
+    /// Formatting options
+    pub format: &'a FormatOptions,
+}
+
+pub trait InstructionRenderer {
+    fn render(&self, insn: &Instruction, ctx: &RenderContext) -> String;
+
+    /// Render a byte that could not be decoded as an instruction.
+    fn render_byte(&self, byte: u8, ctx: &RenderContext) -> String;
+}
+
+/// The default listing: a byte dump followed by the mnemonic and
+/// operands.
+pub struct TextRenderer;
+
+impl InstructionRenderer for TextRenderer {
+    fn render(&self, insn: &Instruction, ctx: &RenderContext) -> String {
+        insn.display(ctx.format).to_string()
+    }
+
+    fn render_byte(&self, byte: u8, ctx: &RenderContext) -> String {
+        format!("{:<width$} | {:<10}0x{:02x}", format!("{:02x}", byte), ".byte", byte,
+                width = ctx.format.pad_width)
+    }
+}
+
+/// GNU as style: a tab-indented, lower case mnemonic and operands, with
+/// no byte dump. PC-relative branch targets are written as symbols
+/// where possible so the output can be reassembled.
+pub struct GasRenderer;
+
+impl InstructionRenderer for GasRenderer {
+    fn render(&self, insn: &Instruction, ctx: &RenderContext) -> String {
+        let mut out = format!("\t{}", insn.name.to_lowercase());
+
+        if insn.operand_count > 0 {
+            out.push('\t');
+
+            match insn.branch_target(ctx.addr) {
+                Some(target) => out.push_str(&ctx.symbols.relative(target)),
+                None => {
+                    for (i, op) in insn.operands[..insn.operand_count as usize].iter().enumerate() {
+                        if i > 0 {
+                            out.push(',');
+                        }
+                        let _ = write!(out, "{}", op.display(ctx.format.reg_style));
+                    }
+                }
+            }
+        }
+
+        out
+    }
+
+    fn render_byte(&self, byte: u8, _ctx: &RenderContext) -> String {
+        format!("\t.byte\t0x{:02x}", byte)
+    }
+}
+
+/// Version of the JSON schema below. Bump it whenever a field is
+/// added, removed, or changes meaning.
+pub const JSON_SCHEMA_VERSION: u32 = 4;
+
+/// The first line of a JSON listing, identifying the format, e.g.
+/// `{"schema":"we32dis-listing","version":4,"tool_version":"0.1.0"}`.
+pub fn json_header() -> String {
+    format!("{{\"schema\":\"we32dis-listing\",\"version\":{},\"tool_version\":\"{}\"}}",
+            JSON_SCHEMA_VERSION, json_escape(crate::VERSION))
+}
+
+/// One JSON object per instruction, on a line of its own.
+///
+/// A listing starts with the header object from `json_header`, giving
+/// the schema name and version and the version of the crate that wrote
+/// it. After that, each instruction is an object with the fields:
+///
+///   - `version`: the schema version, currently 4
+///   - `address`: virtual address of the instruction
+///   - `file_offset`: offset of the instruction in the input file
+///   - `opcode`: numeric opcode (halfword opcodes are e.g. 12357, 0x3045)
+///   - `mnemonic`: the mnemonic, e.g. "MOVW"
+///   - `bytes`: the instruction bytes in hex, separated by spaces
+///   - `operands`: an array of operand objects, in instruction order
+///   - `target`: for PC-relative branches only, the target address
+///
+/// Each operand object has the fields:
+///
+///   - `text`: the operand as it appears in the text listing
+///   - `kind`: one of "register", "register-deferred", "immediate",
+///     "literal", "absolute", "absolute-deferred", "displacement",
+///     "displacement-deferred", or "relative" (the PC-relative literal
+///     of a branch)
+///   - `register`: the register name, e.g. "%fp", or null
+///   - `value`: the embedded number: the immediate or literal value,
+///     displacement, absolute address, or branch displacement.
+///     Literals and displacements are sign-extended; immediates are
+///     sign-extended only under a signed expanded type.
+///   - `type`: the operand's data type: "byte", "half", "word",
+///     "sbyte", "uhalf", "uword", or "none"
+///   - `expanded`: the expanded type in effect, or null
+///
+/// A byte that could not be decoded (see `render_byte`) is an object
+/// with only `version`, `address`, `file_offset`, `bytes`, and
+/// `"data": true`. Version 1 had no such objects, version 3 added
+/// `file_offset`, and version 4 added the header.
+pub struct JsonRenderer;
+
+fn type_name(data: Data) -> &'static str {
+    match data {
+        Data::None => "none",
+        Data::Byte => "byte",
+        Data::Half => "half",
+        Data::Word => "word",
+        Data::SByte => "sbyte",
+        Data::UHalf => "uhalf",
+        Data::UWord => "uword",
+    }
+}
+
+fn operand_kind(op: &Operand) -> &'static str {
+    match op.mode() {
+        AddrMode::Register => "register",
+        AddrMode::RegisterDeferred => "register-deferred",
+        AddrMode::ByteImmediate | AddrMode::HalfwordImmediate | AddrMode::WordImmediate => "immediate",
+        AddrMode::PositiveLiteral | AddrMode::NegativeLiteral => "literal",
+        AddrMode::Absolute => "absolute",
+        AddrMode::AbsoluteDeferred => "absolute-deferred",
+        AddrMode::ByteDisplacement | AddrMode::HalfwordDisplacement | AddrMode::WordDisplacement |
+        AddrMode::APShortOffset | AddrMode::FPShortOffset => "displacement",
+        AddrMode::ByteDisplacementDeferred | AddrMode::HalfwordDisplacementDeferred |
+        AddrMode::WordDisplacementDeferred => "displacement-deferred",
+        AddrMode::None => match op.data_type() {
+            Data::Byte | Data::Half => "relative",
+            _ => "literal",
+        },
+    }
+}
+
+fn operand_value(op: &Operand) -> i64 {
+    let v = op.embedded();
+    let signed = matches!(op.expanded_type(), Some(Data::Word) | Some(Data::Half) | Some(Data::SByte));
+
+    match op.mode() {
+        AddrMode::ByteDisplacement | AddrMode::ByteDisplacementDeferred |
+        AddrMode::NegativeLiteral => i64::from(v as u8 as i8),
+        AddrMode::HalfwordDisplacement | AddrMode::HalfwordDisplacementDeferred => i64::from(v as u16 as i16),
+        AddrMode::WordDisplacement | AddrMode::WordDisplacementDeferred => i64::from(v as i32),
+        AddrMode::ByteImmediate if signed => i64::from(v as u8 as i8),
+        AddrMode::HalfwordImmediate if signed => i64::from(v as u16 as i16),
+        AddrMode::WordImmediate if signed => i64::from(v as i32),
+        AddrMode::None => match op.data_type() {
+            Data::Byte => i64::from(v as u8 as i8),
+            Data::Half => i64::from(v as u16 as i16),
+            _ => i64::from(v),
+        },
+        _ => i64::from(v),
+    }
+}
+
+impl InstructionRenderer for JsonRenderer {
+    fn render(&self, insn: &Instruction, ctx: &RenderContext) -> String {
+        let operands = &insn.operands[..insn.operand_count as usize];
+
+        let mut bytes = if insn.opcode_len() == 2 {
+            format!("{:02x} {:02x}", insn.opcode >> 8, insn.opcode & 0xff)
+        } else {
+            format!("{:02x}", insn.opcode)
+        };
+
+        for b in operands.iter().flat_map(|op| op.bytes()) {
+            let _ = write!(bytes, " {:02x}", b);
+        }
+
+        let mut out = format!(
+            "{{\"version\":{},\"address\":{},\"file_offset\":{},\"opcode\":{},\"mnemonic\":\"{}\",\"bytes\":\"{}\",\"operands\":[",
+            JSON_SCHEMA_VERSION, ctx.addr, ctx.file_offset, insn.opcode, json_escape(insn.name), bytes
+        );
+
+        for (i, op) in operands.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+
+            let register = match op.register() {
+                Some(r) => format!("\"{}\"", register_name(r)),
+                None => String::from("null"),
+            };
+
+            let expanded = match op.expanded_type() {
+                Some(t) => format!("\"{}\"", type_name(t)),
+                None => String::from("null"),
+            };
+
+            let _ = write!(
+                out,
+                "{{\"text\":\"{}\",\"kind\":\"{}\",\"register\":{},\"value\":{},\"type\":\"{}\",\"expanded\":{}}}",
+                json_escape(&op.to_string()), operand_kind(op), register,
+                operand_value(op), type_name(op.data_type()), expanded
+            );
+        }
+
+        out.push(']');
+
+        if let Some(target) = insn.branch_target(ctx.addr) {
+            let _ = write!(out, ",\"target\":{}", target);
+        }
+
+        out.push('}');
+        out
+    }
+
+    fn render_byte(&self, byte: u8, ctx: &RenderContext) -> String {
+        format!("{{\"version\":{},\"address\":{},\"file_offset\":{},\"bytes\":\"{:02x}\",\"data\":true}}",
+                JSON_SCHEMA_VERSION, ctx.addr, ctx.file_offset, byte)
+    }
+}
+
+/// Escape a string for inclusion in a JSON string literal.
+pub fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
@@ -0,0 +1,128 @@
+//!
+//! Mapping between addresses and symbol names
+//!
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default)]
+pub struct SymbolMap {
+    map: BTreeMap<u32, String>,
+}
+
+impl SymbolMap {
+    pub fn new() -> Self {
+        SymbolMap {
+            map: BTreeMap::new(),
+        }
+    }
+
+    ///
+    /// Add a symbol. If another symbol already has this address, the
+    /// first one wins, unless it is a section name such as ".text".
+    ///
+    pub fn insert(&mut self, addr: u32, name: &str) {
+        let replace = match self.map.get(&addr) {
+            Some(existing) => existing.starts_with('.') && !name.starts_with('.'),
+            None => true,
+        };
+
+        if replace {
+            self.map.insert(addr, name.to_owned());
+        }
+    }
+
+    ///
+    /// Add every symbol from another map. Where both maps name the same
+    /// address, `other` wins if `replace` is set, and this map's name is
+    /// kept otherwise.
+    ///
+    pub fn merge(&mut self, other: &SymbolMap, replace: bool) {
+        for (&addr, name) in &other.map {
+            if replace || !self.map.contains_key(&addr) {
+                self.map.insert(addr, name.clone());
+            }
+        }
+    }
+
+    ///
+    /// Parse a symbol file, which has one symbol per line: an address
+    /// in hex, with or without "0x", and a name, separated by spaces or
+    /// tabs. A single-letter type between the two is skipped, so `nm`
+    /// output can be used as it is. Blank lines and anything after a
+    /// '#' are ignored. Later lines for the same address replace
+    /// earlier ones.
+    ///
+    /// On failure, returns the number of the first bad line, counting
+    /// from 1.
+    ///
+    pub fn parse(text: &str) -> Result<SymbolMap, usize> {
+        let mut map = SymbolMap::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            };
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            let (addr, name) = match fields[..] {
+                [] => continue,
+                [addr, name] => (addr, name),
+                [addr, kind, name] if kind.len() == 1 => (addr, name),
+                _ => return Err(i + 1),
+            };
+
+            let digits = addr.strip_prefix("0x")
+                .or_else(|| addr.strip_prefix("0X"))
+                .unwrap_or(addr);
+
+            match u32::from_str_radix(digits, 16) {
+                Ok(addr) => map.map.insert(addr, name.to_owned()),
+                Err(_) => return Err(i + 1),
+            };
+        }
+
+        Ok(map)
+    }
+
+    /// The address of the named symbol. If several symbols share the
+    /// name, the one with the lowest address is returned.
+    pub fn address_of(&self, name: &str) -> Option<u32> {
+        self.map.iter().find(|(_, n)| *n == name).map(|(&addr, _)| addr)
+    }
+
+    pub fn get(&self, addr: u32) -> Option<&str> {
+        self.map.get(&addr).map(|s| s.as_str())
+    }
+
+    ///
+    /// Find the nearest symbol at or before the address, returning its
+    /// name and the address's offset from it.
+    ///
+    pub fn nearest(&self, addr: u32) -> Option<(&str, u32)> {
+        self.map.range(..=addr).next_back()
+            .map(|(a, name)| (name.as_str(), addr - a))
+    }
+
+    ///
+    /// Format an address relative to the nearest preceding symbol, e.g.
+    /// "_main+0x1c". Addresses with no preceding symbol are printed in
+    /// hex.
+    ///
+    pub fn relative(&self, addr: u32) -> String {
+        match self.nearest(addr) {
+            Some((name, 0)) => name.to_owned(),
+            Some((name, offset)) => format!("{}+0x{:x}", name, offset),
+            None => format!("0x{:08x}", addr),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
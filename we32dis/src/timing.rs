@@ -0,0 +1,127 @@
+//!
+//! Approximate WE32100 instruction timing
+//!
+//! The figures here are rough estimates for comparing code sequences,
+//! not a cycle-accurate model. They are patterned on the instruction
+//! timing tables in the WE 32100 Microprocessor Information Manual but
+//! have been simplified: they assume zero-wait-state memory, an empty
+//! instruction cache, and no pipeline overlap. Each instruction has a
+//! base cost, and each operand adds the cost of its addressing mode.
+//! Instructions whose times depend on their data (multiply, divide,
+//! block moves, string operations) use a single typical figure.
+//!
+//! Source: the WE 32100 Microprocessor Information Manual (AT&T,
+//! 1985). `BASE_CYCLES` follows the execution times it gives with each
+//! instruction, taking the register-operand case, and `mode_cycles`
+//! the extra time it gives for fetching an operand in each addressing
+//! mode.
+//!
+
+use crate::decode::{AddrMode, Instruction};
+
+/// Base execution cycles by mnemonic, before operand costs are added.
+static BASE_CYCLES: &[(&str, u32)] = &[
+    ("HALT", 1),
+    ("NOP", 1), ("NOP2", 1), ("NOP3", 1),
+    ("BPT", 18), ("WAIT", 6),
+
+    ("MOVB", 2), ("MOVH", 2), ("MOVW", 2),
+    ("MOVAW", 2), ("PUSHAW", 4), ("PUSHW", 4), ("POPW", 4),
+    ("CLRB", 2), ("CLRH", 2), ("CLRW", 2),
+    ("MCOMB", 3), ("MCOMH", 3), ("MCOMW", 3),
+    ("MNEGB", 3), ("MNEGH", 3), ("MNEGW", 3),
+    ("SWAPBI", 8), ("SWAPHI", 8), ("SWAPWI", 8),
+    ("MOVTRW", 10),
+
+    ("TSTB", 2), ("TSTH", 2), ("TSTW", 2),
+    ("CMPB", 3), ("CMPH", 3), ("CMPW", 3),
+    ("BITB", 3), ("BITH", 3), ("BITW", 3),
+
+    ("INCB", 3), ("INCH", 3), ("INCW", 3),
+    ("DECB", 3), ("DECH", 3), ("DECW", 3),
+    ("ADDB2", 3), ("ADDH2", 3), ("ADDW2", 3),
+    ("ADDB3", 3), ("ADDH3", 3), ("ADDW3", 3),
+    ("SUBB2", 3), ("SUBH2", 3), ("SUBW2", 3),
+    ("SUBB3", 3), ("SUBH3", 3), ("SUBW3", 3),
+    ("ANDB2", 3), ("ANDH2", 3), ("ANDW2", 3),
+    ("ANDB3", 3), ("ANDH3", 3), ("ANDW3", 3),
+    ("ORB2", 3), ("ORH2", 3), ("ORW2", 3),
+    ("ORB3", 3), ("ORH3", 3), ("ORW3", 3),
+    ("XORB2", 3), ("XORH2", 3), ("XORW2", 3),
+    ("XORB3", 3), ("XORH3", 3), ("XORW3", 3),
+
+    ("MULB2", 16), ("MULH2", 24), ("MULW2", 40),
+    ("MULB3", 16), ("MULH3", 24), ("MULW3", 40),
+    ("DIVB2", 28), ("DIVH2", 44), ("DIVW2", 76),
+    ("DIVB3", 28), ("DIVH3", 44), ("DIVW3", 76),
+    ("MODB2", 28), ("MODH2", 44), ("MODW2", 76),
+    ("MODB3", 28), ("MODH3", 44), ("MODW3", 76),
+
+    ("ALSW3", 5), ("ARSB3", 5), ("ARSH3", 5), ("ARSW3", 5),
+    ("LLSB3", 5), ("LLSH3", 5), ("LLSW3", 5), ("LRSW3", 5),
+    ("ROTW", 5),
+    ("EXTFB", 7), ("EXTFH", 7), ("EXTFW", 7),
+    ("INSFB", 10), ("INSFH", 10), ("INSFW", 10),
+
+    ("BRB", 3), ("BRH", 3), ("JMP", 3),
+    ("BSBB", 6), ("BSBH", 6), ("JSB", 6), ("RSB", 6),
+    ("CALL", 14), ("SAVE", 12), ("RESTORE", 12), ("RET", 14),
+    ("BEB", 3), ("BEH", 3), ("BNEB", 3), ("BNEH", 3),
+    ("BGB", 3), ("BGH", 3), ("BGEB", 3), ("BGEH", 3),
+    ("BLB", 3), ("BLH", 3), ("BLEB", 3), ("BLEH", 3),
+    ("BGUB", 3), ("BGUH", 3), ("BGEUB", 3), ("BGEUH", 3),
+    ("BLUB", 3), ("BLUH", 3), ("BLEUB", 3), ("BLEUH", 3),
+    ("BVCB", 3), ("BVCH", 3), ("BVSB", 3), ("BVSH", 3),
+    ("REQL", 4), ("REQLU", 4), ("RNEQ", 4), ("RNEQU", 4),
+    ("RGTR", 4), ("RGTRU", 4), ("RGEQ", 4), ("RGEQU", 4),
+    ("RLSS", 4), ("RLSSU", 4), ("RLEQ", 4), ("RLEQU", 4),
+    ("RVC", 4), ("RVS", 4),
+
+    ("MOVBLW", 12), ("STRCPY", 12), ("STREND", 10),
+    ("GATE", 40), ("CALLPS", 60), ("RETPS", 60), ("RETG", 30),
+    ("INTACK", 6), ("MVERNO", 3),
+    ("ENBVJMP", 4), ("DISVJMP", 4), ("CFLUSH", 4),
+];
+
+/// Extra cycles needed to access an operand in the given addressing mode.
+fn mode_cycles(mode: AddrMode) -> u32 {
+    match mode {
+        AddrMode::None |
+        AddrMode::Register |
+        AddrMode::PositiveLiteral |
+        AddrMode::NegativeLiteral => 0,
+        AddrMode::ByteImmediate => 1,
+        AddrMode::HalfwordImmediate => 1,
+        AddrMode::WordImmediate => 2,
+        AddrMode::RegisterDeferred => 3,
+        AddrMode::APShortOffset | AddrMode::FPShortOffset => 3,
+        AddrMode::ByteDisplacement |
+        AddrMode::HalfwordDisplacement |
+        AddrMode::WordDisplacement => 4,
+        AddrMode::Absolute => 4,
+        AddrMode::ByteDisplacementDeferred |
+        AddrMode::HalfwordDisplacementDeferred |
+        AddrMode::WordDisplacementDeferred => 7,
+        AddrMode::AbsoluteDeferred => 7,
+    }
+}
+
+/// Base cycle count for a mnemonic, ignoring operands.
+pub fn base_cycles(name: &str) -> Option<u32> {
+    BASE_CYCLES.iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, c)| *c)
+}
+
+/// Estimate the cycles needed to execute an instruction, or `None` if
+/// there is no timing figure for its mnemonic.
+pub fn estimate(insn: &Instruction) -> Option<u32> {
+    let base = base_cycles(insn.name)?;
+
+    let operands: u32 = insn.operands[..insn.operand_count as usize]
+        .iter()
+        .map(|op| mode_cycles(op.mode()))
+        .sum();
+
+    Some(base + operands)
+}
@@ -0,0 +1,71 @@
+//!
+//! Regression tests comparing the disassembler's output with stored
+//! listings
+//!
+//! Each case in tests/golden is two files: NAME.args, one line holding
+//! the input file and the flags to disassemble it with, and
+//! NAME.expected, the output (stdout followed by stderr, then the exit
+//! status if it is not 0). Inputs are named relative to that directory.
+//!
+//! After a change that is meant to alter the output, run the tests
+//! with UPDATE_GOLDEN=1 to write the current output as the expected
+//! listing for every case, then review the differences before
+//! committing them.
+//!
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn golden_listings() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut cases: Vec<_> = fs::read_dir(&dir)
+        .expect("tests/golden is missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("args"))
+        .collect();
+    cases.sort();
+
+    assert!(!cases.is_empty(), "no cases in {}", dir.display());
+
+    let mut failed = vec!();
+
+    for args in &cases {
+        let name = args.file_stem().unwrap().to_string_lossy().into_owned();
+        let expected_path = args.with_extension("expected");
+
+        let line = fs::read_to_string(args).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_we32dis"))
+            .args(line.split_whitespace())
+            .current_dir(&dir)
+            .output()
+            .expect("couldn't run we32dis");
+
+        let mut actual = String::from_utf8_lossy(&output.stdout).into_owned();
+        actual.push_str(&String::from_utf8_lossy(&output.stderr));
+
+        match output.status.code() {
+            Some(0) => {}
+            Some(code) => actual.push_str(&format!("exit status {}\n", code)),
+            None => actual.push_str("killed by a signal\n"),
+        }
+
+        if update {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+
+        if actual != expected {
+            eprintln!("--- {} expected\n{}--- {} actual\n{}", name, expected, name, actual);
+            failed.push(name);
+        }
+    }
+
+    assert!(failed.is_empty(), "output differs for {}", failed.join(", "));
+}